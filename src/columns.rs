@@ -0,0 +1,525 @@
+use super::statements;
+use super::{
+    ComparisonOperator, Keyword, LiteralValueTypeIndicator, LogicalOperator, Operator, Sql, Token,
+    TokenTree,
+};
+
+/// A column that is referenced in one of the clauses that filter, join,
+/// sort or group rows.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnUsage {
+    /// Table, alias or schema and table in front of the column name
+    pub qualifier: Option<String>,
+    pub name: String,
+    pub clause: Clause,
+    /// How the column is compared, if it is compared
+    pub operator: Option<ColumnOperator>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Clause {
+    Where,   // WHERE
+    JoinOn,  // JOIN ... ON
+    OrderBy, // ORDER BY
+    GroupBy, // GROUP BY
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColumnOperator {
+    Equal,    // =, ==, <=>
+    NotEqual, // !=, <>
+    In,       // IN, NOT IN
+    Like,     // LIKE, ILIKE, RLIKE, GLOB, MATCH, REGEXP
+    Range,    // <, >, <=, >=, BETWEEN
+    Is,       // IS NULL, IS NOT NULL, IS DISTINCT FROM
+}
+
+// Keywords that can't be a column name in the clauses we look at
+const NOT_COLUMNS: &[&str] = &[
+    "all",
+    "any",
+    "as",
+    "asc",
+    "by",
+    "case",
+    "collate",
+    "cube",
+    "current_date",
+    "current_time",
+    "current_timestamp",
+    "desc",
+    "distinct",
+    "end",
+    "escape",
+    "exists",
+    "first",
+    "grouping",
+    "interval",
+    "is",
+    "last",
+    "localtime",
+    "localtimestamp",
+    "nulls",
+    "rollup",
+    "sets",
+    "some",
+    "when",
+];
+
+// Keywords that end the clauses we look at
+const END_OF_CLAUSE: &[&str] = &[
+    "cross",
+    "except",
+    "fetch",
+    "for",
+    "full",
+    "having",
+    "intersect",
+    "left",
+    "lock",
+    "natural",
+    "outer",
+    "returning",
+    "right",
+    "union",
+    "using",
+    "window",
+];
+
+pub fn columns(sql: &Sql) -> Vec<Vec<ColumnUsage>> {
+    let tree = TokenTree::new(sql);
+    statements::split(sql)
+        .iter()
+        .map(|range| statement_columns(sql, &tree, range.start, range.end))
+        .collect()
+}
+
+fn statement_columns(sql: &Sql, tree: &TokenTree, start: usize, end: usize) -> Vec<ColumnUsage> {
+    let positions: Vec<usize> = (start..end)
+        .filter(|pos| {
+            !matches!(
+                sql.tokens[*pos],
+                Token::Space | Token::Newline | Token::None | Token::Comment(_)
+            )
+        })
+        .collect();
+    let walker = Walker {
+        sql,
+        tree,
+        positions: &positions,
+    };
+
+    let mut usages = Vec::new();
+    let mut clause = None;
+    // The clause we were in when opening each of the parentheses we're in
+    let mut scopes = Vec::new();
+    let mut i = 0;
+    while i < positions.len() {
+        let token = walker.token(i);
+        match token {
+            Token::ParentheseOpen => scopes.push(clause),
+            Token::ParentheseClose => {
+                if let Some(outer) = scopes.pop() {
+                    clause = outer;
+                }
+            }
+            Token::Keyword(Keyword::Where) => clause = Some(Clause::Where),
+            Token::Keyword(Keyword::On) => {
+                // Not a join in ON CONFLICT or ON DUPLICATE KEY UPDATE
+                let upsert =
+                    walker.keyword_at(i + 1, "conflict") || walker.keyword_at(i + 1, "duplicate");
+                clause = if upsert { None } else { Some(Clause::JoinOn) };
+            }
+            Token::Keyword(
+                Keyword::Select
+                | Keyword::From
+                | Keyword::Join
+                | Keyword::Inner
+                | Keyword::Limit
+                | Keyword::Offset
+                | Keyword::Insert
+                | Keyword::Into
+                | Keyword::Values
+                | Keyword::Update
+                | Keyword::Set,
+            ) => clause = None,
+            token if sql.is_keyword(token, "order") && walker.keyword_at(i + 1, "by") => {
+                clause = Some(Clause::OrderBy);
+                i += 1;
+            }
+            token if sql.is_keyword(token, "group") && walker.keyword_at(i + 1, "by") => {
+                clause = Some(Clause::GroupBy);
+                i += 1;
+            }
+            token if END_OF_CLAUSE.iter().any(|k| sql.is_keyword(token, k)) => clause = None,
+            _ => {
+                if let Some(clause) = clause {
+                    if let Some((usage, next)) = walker.column(i, clause) {
+                        usages.push(usage);
+                        i = next;
+                        continue;
+                    }
+                }
+            }
+        }
+        i += 1;
+    }
+
+    usages
+}
+
+// Walks the significant tokens of a statement, `i` is an index in
+// `positions` which are positions in `Sql::tokens`.
+struct Walker<'a> {
+    sql: &'a Sql,
+    tree: &'a TokenTree,
+    positions: &'a [usize],
+}
+
+impl<'a> Walker<'a> {
+    fn token(&self, i: usize) -> &'a Token {
+        &self.sql.tokens[self.positions[i]]
+    }
+
+    fn get(&self, i: usize) -> Option<&'a Token> {
+        self.positions.get(i).map(|pos| &self.sql.tokens[*pos])
+    }
+
+    fn keyword_at(&self, i: usize, keyword: &str) -> bool {
+        self.get(i)
+            .is_some_and(|token| self.sql.is_keyword(token, keyword))
+    }
+
+    // The name if the token at `i` can be an identifier
+    fn identifier(&self, i: usize) -> Option<String> {
+        let name = match self.get(i)? {
+            Token::Keyword(Keyword::Other(slice)) => {
+                let name = self.sql.buffer_content(slice);
+                if NOT_COLUMNS.iter().any(|k| name.eq_ignore_ascii_case(k)) {
+                    return None;
+                }
+                name
+            }
+            Token::Backticked(slice) | Token::DoubleQuoted(slice) => self.sql.buffer_content(slice),
+            // Short or common column names are lexed as a type indicator
+            // when they're not followed by a value.
+            Token::LiteralValueTypeIndicator(indicator) => {
+                if let Some(Token::SingleQuoted(_)) = self.get(i + 1) {
+                    return None;
+                }
+                match indicator {
+                    LiteralValueTypeIndicator::Date => "date",
+                    LiteralValueTypeIndicator::Time => "time",
+                    LiteralValueTypeIndicator::Timestamp => "timestamp",
+                    LiteralValueTypeIndicator::X => "x",
+                    LiteralValueTypeIndicator::B => "b",
+                    LiteralValueTypeIndicator::N => "n",
+                    _ => return None,
+                }
+            }
+            _ => return None,
+        };
+        Some(name.to_string())
+    }
+
+    // Whether the token at `i` closes the arguments of a function call
+    fn closes_function_call(&self, i: usize) -> bool {
+        if self.get(i) != Some(&Token::ParentheseClose) {
+            return false;
+        }
+        match self.tree.enclosing(self.positions[i]) {
+            Some(open) => match self.positions.iter().position(|pos| *pos == open) {
+                Some(open_i) if open_i > 0 => self.identifier(open_i - 1).is_some(),
+                _ => false,
+            },
+            None => false,
+        }
+    }
+
+    // Reads a possibly qualified column reference starting at `i`. Returns
+    // the usage and the index after the reference.
+    fn column(&self, i: usize, clause: Clause) -> Option<(ColumnUsage, usize)> {
+        // Casts such as `::regclass` name a type
+        if i > 0 && self.get(i - 1) == Some(&Token::Colon) {
+            return None;
+        }
+
+        let mut parts = vec![self.identifier(i)?];
+        let mut next = i + 1;
+        while self.get(next) == Some(&Token::Dot) {
+            match self.identifier(next + 1) {
+                Some(part) => {
+                    parts.push(part);
+                    next += 2;
+                }
+                // Something like `table.*`
+                None => return None,
+            }
+        }
+
+        // A function call, its arguments are handled on their own
+        if self.get(next) == Some(&Token::ParentheseOpen) {
+            return None;
+        }
+
+        let name = parts.pop().expect("at least one part");
+        let qualifier = if parts.is_empty() {
+            None
+        } else {
+            Some(parts.join("."))
+        };
+
+        let mut after = next;
+        while self.closes_function_call(after) {
+            after += 1;
+        }
+        let operator = self.operator_at(after).or_else(|| {
+            // The column is on the right hand side of a comparison
+            let mut before = i;
+            // Skip over parentheses and the names of function calls
+            while before > 0 && self.get(before - 1) == Some(&Token::ParentheseOpen) {
+                before -= 1;
+                if before > 0 && self.identifier(before - 1).is_some() {
+                    before -= 1;
+                }
+            }
+            match before {
+                0 => None,
+                before => match self.get(before - 1) {
+                    Some(Token::Operator(Operator::Comparison(_))) => self.operator_at(before - 1),
+                    _ => None,
+                },
+            }
+        });
+
+        Some((
+            ColumnUsage {
+                qualifier,
+                name,
+                clause,
+                operator,
+            },
+            next,
+        ))
+    }
+
+    fn operator_at(&self, i: usize) -> Option<ColumnOperator> {
+        match self.get(i)? {
+            Token::Operator(Operator::Comparison(operator)) => Some(match operator {
+                ComparisonOperator::Equal
+                | ComparisonOperator::Equal2
+                | ComparisonOperator::NullSafeEqual => ColumnOperator::Equal,
+                ComparisonOperator::NotEqual | ComparisonOperator::EqualWithArrows => {
+                    ColumnOperator::NotEqual
+                }
+                ComparisonOperator::GreaterThan
+                | ComparisonOperator::LessThan
+                | ComparisonOperator::GreaterThanOrEqual
+                | ComparisonOperator::LessThanOrEqual
+                | ComparisonOperator::EqualOrGreaterThan
+                | ComparisonOperator::EqualOrLessThan => ColumnOperator::Range,
+            }),
+            Token::Operator(Operator::Logical(LogicalOperator::In)) => Some(ColumnOperator::In),
+            Token::Operator(Operator::Logical(
+                LogicalOperator::Like
+                | LogicalOperator::Ilike
+                | LogicalOperator::Rlike
+                | LogicalOperator::Glob
+                | LogicalOperator::Match
+                | LogicalOperator::Regexp,
+            )) => Some(ColumnOperator::Like),
+            Token::Operator(Operator::Logical(LogicalOperator::Not)) => self.operator_at(i + 1),
+            Token::Keyword(Keyword::Between) => Some(ColumnOperator::Range),
+            token if self.sql.is_keyword(token, "is") => Some(ColumnOperator::Is),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{columns, lex};
+    use super::{Clause, ColumnOperator, ColumnUsage};
+
+    fn usage(
+        qualifier: Option<&str>,
+        name: &str,
+        clause: Clause,
+        operator: Option<ColumnOperator>,
+    ) -> ColumnUsage {
+        ColumnUsage {
+            qualifier: qualifier.map(|q| q.to_string()),
+            name: name.to_string(),
+            clause,
+            operator,
+        }
+    }
+
+    fn single_statement(sql: &str) -> Vec<ColumnUsage> {
+        let mut statements = columns(&lex(sql.to_string()));
+        assert_eq!(statements.len(), 1);
+        statements.remove(0)
+    }
+
+    #[test]
+    fn test_where_operators() {
+        assert_eq!(
+            single_statement(
+                "SELECT `a` FROM `table` WHERE `id` = 1 AND name LIKE 'x%' AND `status` IN (1, 2) AND created_at BETWEEN 1 AND 2 AND age >= 18 AND kind != 'y' AND deleted_at IS NULL"
+            ),
+            vec![
+                usage(None, "id", Clause::Where, Some(ColumnOperator::Equal)),
+                usage(None, "name", Clause::Where, Some(ColumnOperator::Like)),
+                usage(None, "status", Clause::Where, Some(ColumnOperator::In)),
+                usage(None, "created_at", Clause::Where, Some(ColumnOperator::Range)),
+                usage(None, "age", Clause::Where, Some(ColumnOperator::Range)),
+                usage(None, "kind", Clause::Where, Some(ColumnOperator::NotEqual)),
+                usage(None, "deleted_at", Clause::Where, Some(ColumnOperator::Is)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_where_not() {
+        assert_eq!(
+            single_statement("SELECT * FROM t WHERE a NOT IN (1) AND b NOT LIKE 'x' AND NOT c"),
+            vec![
+                usage(None, "a", Clause::Where, Some(ColumnOperator::In)),
+                usage(None, "b", Clause::Where, Some(ColumnOperator::Like)),
+                usage(None, "c", Clause::Where, None),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_qualified() {
+        assert_eq!(
+            single_statement(
+                "SELECT * FROM \"public\".\"users\" WHERE \"public\".\"users\".\"id\" = $1 AND u.email = ?"
+            ),
+            vec![
+                usage(
+                    Some("public.users"),
+                    "id",
+                    Clause::Where,
+                    Some(ColumnOperator::Equal)
+                ),
+                usage(Some("u"), "email", Clause::Where, Some(ColumnOperator::Equal)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_join_on() {
+        assert_eq!(
+            single_statement(
+                "SELECT * FROM `posts` INNER JOIN `users` ON `posts`.`user_id` = `users`.`id` LEFT JOIN c ON c.post_id = posts.id AND c.visible = TRUE WHERE `users`.`active` = 1"
+            ),
+            vec![
+                usage(
+                    Some("posts"),
+                    "user_id",
+                    Clause::JoinOn,
+                    Some(ColumnOperator::Equal)
+                ),
+                usage(
+                    Some("users"),
+                    "id",
+                    Clause::JoinOn,
+                    Some(ColumnOperator::Equal)
+                ),
+                usage(Some("c"), "post_id", Clause::JoinOn, Some(ColumnOperator::Equal)),
+                usage(Some("posts"), "id", Clause::JoinOn, Some(ColumnOperator::Equal)),
+                usage(Some("c"), "visible", Clause::JoinOn, Some(ColumnOperator::Equal)),
+                usage(Some("users"), "active", Clause::Where, Some(ColumnOperator::Equal)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_order_and_group_by() {
+        assert_eq!(
+            single_statement(
+                "SELECT status, COUNT(*) FROM t WHERE a > 1 GROUP BY status, t.kind ORDER BY created_at DESC, 2 ASC NULLS LAST LIMIT 10"
+            ),
+            vec![
+                usage(None, "a", Clause::Where, Some(ColumnOperator::Range)),
+                usage(None, "status", Clause::GroupBy, None),
+                usage(Some("t"), "kind", Clause::GroupBy, None),
+                usage(None, "created_at", Clause::OrderBy, None),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_function_call() {
+        assert_eq!(
+            single_statement("SELECT * FROM t WHERE LOWER(email) = 'x' AND 'y' = UPPER(name)"),
+            vec![
+                usage(None, "email", Clause::Where, Some(ColumnOperator::Equal)),
+                usage(None, "name", Clause::Where, Some(ColumnOperator::Equal)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_subquery() {
+        assert_eq!(
+            single_statement(
+                "SELECT * FROM t WHERE id IN (SELECT t_id FROM o WHERE o.kind = 1) AND b = 2"
+            ),
+            vec![
+                usage(None, "id", Clause::Where, Some(ColumnOperator::In)),
+                usage(
+                    Some("o"),
+                    "kind",
+                    Clause::Where,
+                    Some(ColumnOperator::Equal)
+                ),
+                usage(None, "b", Clause::Where, Some(ColumnOperator::Equal)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_short_names_and_casts() {
+        assert_eq!(
+            single_statement(
+                "SELECT * FROM t WHERE t.b = 1 AND date > DATE '2024-01-01' AND a.attrelid = '\"value\"'::regclass"
+            ),
+            vec![
+                usage(Some("t"), "b", Clause::Where, Some(ColumnOperator::Equal)),
+                usage(None, "date", Clause::Where, Some(ColumnOperator::Range)),
+                usage(Some("a"), "attrelid", Clause::Where, Some(ColumnOperator::Equal)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_upsert_is_not_a_join() {
+        assert_eq!(
+            single_statement("INSERT INTO t (a) VALUES (1) ON CONFLICT (a) DO UPDATE SET a = 2"),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn test_each_statement() {
+        let statements = columns(&lex(
+            "UPDATE t SET a = 1 WHERE id = 2; DELETE FROM t WHERE b IN (1)".to_string(),
+        ));
+
+        assert_eq!(
+            statements,
+            vec![
+                vec![usage(
+                    None,
+                    "id",
+                    Clause::Where,
+                    Some(ColumnOperator::Equal)
+                )],
+                vec![usage(None, "b", Clause::Where, Some(ColumnOperator::In))],
+            ]
+        );
+    }
+}
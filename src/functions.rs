@@ -0,0 +1,222 @@
+use std::ops::Range;
+
+use super::statements::is_significant;
+use super::{Keyword, LiteralValueTypeIndicator, Sql, Token, TokenTree};
+
+/// A call to a function such as `LOWER(email)` or `NOW()`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FunctionCall {
+    /// Name of the function as written in the query, including a schema
+    /// if it is qualified.
+    pub name: String,
+    pub arg_count: usize,
+    /// Positions in `Sql::tokens` from the name up to and including the
+    /// closing parenthese.
+    pub span: Range<usize>,
+}
+
+// Keywords that can be followed by parentheses without being a function
+const NOT_FUNCTIONS: &[&str] = &[
+    "as", "exists", "filter", "key", "over", "unique", "using", "within",
+];
+
+// Keywords after which a name followed by parentheses is a table
+const BEFORE_TABLE: &[&str] = &["exists", "references", "table", "with"];
+
+pub fn functions(sql: &Sql) -> Vec<FunctionCall> {
+    let tree = TokenTree::new(sql);
+    let positions: Vec<usize> = (0..sql.tokens.len())
+        .filter(|pos| is_significant(&sql.tokens[*pos]))
+        .collect();
+
+    let mut calls = Vec::new();
+    for (i, pos) in positions.iter().enumerate() {
+        if sql.tokens[*pos] != Token::ParentheseOpen || i == 0 {
+            continue;
+        }
+
+        // Walk back over the name, which can be qualified with a schema
+        let mut start = i - 1;
+        if !is_name(&sql.tokens[positions[start]]) {
+            continue;
+        }
+        while start >= 2
+            && sql.tokens[positions[start - 1]] == Token::Dot
+            && is_name(&sql.tokens[positions[start - 2]])
+        {
+            start -= 2;
+        }
+        let name = positions[start..i]
+            .iter()
+            .map(|pos| name_content(sql, &sql.tokens[*pos]))
+            .collect::<String>();
+
+        if NOT_FUNCTIONS
+            .iter()
+            .any(|keyword| name.eq_ignore_ascii_case(keyword))
+        {
+            continue;
+        }
+        if start > 0 && is_before_table(sql, &sql.tokens[positions[start - 1]]) {
+            continue;
+        }
+
+        let close = tree.closing(*pos);
+        let end = close.unwrap_or(sql.tokens.len() - 1);
+        calls.push(FunctionCall {
+            name,
+            arg_count: arg_count(sql, &tree, *pos, end),
+            span: positions[start]..end + 1,
+        });
+    }
+
+    calls
+}
+
+fn is_name(token: &Token) -> bool {
+    match token {
+        Token::Keyword(Keyword::Other(_)) => true,
+        // DATE(created_at) and friends
+        Token::LiteralValueTypeIndicator(
+            LiteralValueTypeIndicator::Date
+            | LiteralValueTypeIndicator::Time
+            | LiteralValueTypeIndicator::Timestamp,
+        ) => true,
+        _ => false,
+    }
+}
+
+fn name_content<'a>(sql: &'a Sql, token: &Token) -> &'a str {
+    match token {
+        Token::Keyword(Keyword::Other(slice)) => sql.buffer_content(slice),
+        Token::LiteralValueTypeIndicator(LiteralValueTypeIndicator::Date) => "DATE",
+        Token::LiteralValueTypeIndicator(LiteralValueTypeIndicator::Time) => "TIME",
+        Token::LiteralValueTypeIndicator(LiteralValueTypeIndicator::Timestamp) => "TIMESTAMP",
+        _ => ".",
+    }
+}
+
+// INSERT INTO table (a, b), CREATE TABLE table (a int), ...
+fn is_before_table(sql: &Sql, token: &Token) -> bool {
+    match token {
+        Token::Keyword(Keyword::Into | Keyword::Update | Keyword::From | Keyword::Join) => true,
+        token => BEFORE_TABLE
+            .iter()
+            .any(|keyword| sql.is_keyword(token, keyword)),
+    }
+}
+
+// Number of arguments between the parentheses at `open` and `close`, commas
+// in nested groups separate arguments of a nested call.
+pub(crate) fn arg_count(sql: &Sql, tree: &TokenTree, open: usize, close: usize) -> usize {
+    let mut count = 0;
+    let mut in_argument = false;
+    for pos in open + 1..=close {
+        let token = &sql.tokens[pos];
+        if pos == close && *token == Token::ParentheseClose {
+            break;
+        }
+        if !is_significant(token) {
+            continue;
+        }
+        if *token == Token::Comma && tree.enclosing(pos) == Some(open) {
+            in_argument = false;
+        } else if !in_argument {
+            in_argument = true;
+            count += 1;
+        }
+    }
+    count
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{functions, lex};
+    use super::FunctionCall;
+
+    fn names_and_args(sql: &str) -> Vec<(String, usize)> {
+        functions(&lex(sql.to_string()))
+            .into_iter()
+            .map(|call| (call.name, call.arg_count))
+            .collect()
+    }
+
+    #[test]
+    fn test_functions() {
+        assert_eq!(
+            names_and_args(
+                "SELECT COUNT(*), NOW(), COALESCE(a, b, 'c') FROM t WHERE LOWER(email) = ? ORDER BY RANDOM()"
+            ),
+            vec![
+                ("COUNT".to_string(), 1),
+                ("NOW".to_string(), 0),
+                ("COALESCE".to_string(), 3),
+                ("LOWER".to_string(), 1),
+                ("RANDOM".to_string(), 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_span() {
+        // 0: SELECT, 1: space, 2: LOWER, 3: (, 4: a, 5: ), 6: space, 7: FROM
+        assert_eq!(
+            functions(&lex("SELECT LOWER(a) FROM t".to_string())),
+            vec![FunctionCall {
+                name: "LOWER".to_string(),
+                arg_count: 1,
+                span: 2..6,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_nested() {
+        assert_eq!(
+            names_and_args("SELECT CONCAT(UPPER(a), SUBSTR(b, 1, 2), (c, d)) FROM t"),
+            vec![
+                ("CONCAT".to_string(), 3),
+                ("UPPER".to_string(), 1),
+                ("SUBSTR".to_string(), 3),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_qualified_and_spaced() {
+        assert_eq!(
+            names_and_args("SELECT pg_catalog.lower (a), DATE(created_at) FROM t"),
+            vec![("pg_catalog.lower".to_string(), 1), ("DATE".to_string(), 1)]
+        );
+    }
+
+    #[test]
+    fn test_not_functions() {
+        assert_eq!(
+            names_and_args(
+                "WITH x AS (SELECT 1) INSERT INTO t (a, b) SELECT * FROM x WHERE id IN (1, 2) AND EXISTS (SELECT 1)"
+            ),
+            vec![]
+        );
+        assert_eq!(
+            names_and_args("CREATE TABLE IF NOT EXISTS t (id int, name varchar(10))"),
+            vec![("varchar".to_string(), 1)]
+        );
+        assert_eq!(
+            names_and_args("SELECT ROW_NUMBER() OVER (PARTITION BY a) FROM t"),
+            vec![("ROW_NUMBER".to_string(), 0)]
+        );
+    }
+
+    #[test]
+    fn test_unclosed() {
+        assert_eq!(
+            functions(&lex("SELECT MAX(a, b".to_string())),
+            vec![FunctionCall {
+                name: "MAX".to_string(),
+                arg_count: 2,
+                span: 2..8,
+            }]
+        );
+    }
+}
@@ -1,6 +1,7 @@
 use super::{
-    ArithmeticOperator, BitwiseOperator, BufferSlice, ComparisonOperator, JsonOperator, Keyword,
-    LiteralValueTypeIndicator, LogicalOperator, Operator, Sql, Token,
+    ArithmeticOperator, BitwiseOperator, BufferSlice, ComparisonOperator, DiagnosticKind,
+    JsonOperator, Keyword, LexDiagnostic, LexError, Limits, LiteralValueTypeIndicator,
+    LogicalOperator, Operator, Placeholder, Sql, Token,
 };
 
 #[derive(Clone, PartialEq)]
@@ -10,6 +11,14 @@ enum State {
     PastFrom,
 }
 
+// The limit that made the lexer stop early.
+#[derive(Clone, Copy, PartialEq)]
+enum Exceeded {
+    Bytes(usize),
+    Tokens,
+    Nesting,
+}
+
 #[derive(Clone)]
 pub struct SqlLexer {
     state: State,
@@ -17,10 +26,29 @@ pub struct SqlLexer {
     char_indices: Vec<(usize, char)>,
     len: usize,
     pos: usize,
+    limits: Limits,
+    exceeded: Option<Exceeded>,
+    diagnostics: Vec<LexDiagnostic>,
 }
 
 impl SqlLexer {
     pub fn new(buf: String) -> SqlLexer {
+        SqlLexer::with_limits(buf, Limits::default())
+    }
+
+    pub fn with_limits(mut buf: String, limits: Limits) -> SqlLexer {
+        // Cut off the buffer before we allocate anything that grows with
+        // its size, so the work we do is bounded by `max_bytes`.
+        let mut exceeded = None;
+        if buf.len() > limits.max_bytes {
+            exceeded = Some(Exceeded::Bytes(buf.len()));
+            let mut end = limits.max_bytes;
+            while !buf.is_char_boundary(end) {
+                end -= 1;
+            }
+            buf.truncate(end);
+        }
+
         let char_indices: Vec<(usize, char)> = buf.char_indices().collect();
         let len = char_indices.len();
         SqlLexer {
@@ -29,6 +57,9 @@ impl SqlLexer {
             char_indices,
             len,
             pos: 0,
+            limits,
+            exceeded,
+            diagnostics: Vec::new(),
         }
     }
 
@@ -64,6 +95,7 @@ impl SqlLexer {
         mut current_byte_offset: usize,
         delimiter: char,
     ) -> usize {
+        let start_byte_offset = current_byte_offset;
         let mut escape_char_count = 0;
         self.pos += 1;
         loop {
@@ -72,6 +104,10 @@ impl SqlLexer {
                 if self.pos > 0 {
                     current_byte_offset += self.char_indices[self.pos - 1].1.len_utf8();
                 }
+                self.diagnostic(
+                    DiagnosticKind::UnterminatedQuote(delimiter),
+                    start_byte_offset,
+                );
                 break;
             }
             let indice = self.char_indices[self.pos];
@@ -89,15 +125,92 @@ impl SqlLexer {
         current_byte_offset
     }
 
+    // Returns the opening tag of a dollar quoted string such as `$$` or
+    // `$body$` starting at the current position, if there is one.
+    fn dollar_quote_tag(&self) -> Option<&str> {
+        let start_byte_offset = self.char_indices[self.pos].0;
+        for (i, (byte_offset, c)) in self.char_indices[self.pos + 1..].iter().enumerate() {
+            match c {
+                '$' => return Some(&self.buf[start_byte_offset..byte_offset + 1]),
+                c if c.is_alphabetic() || *c == '_' => (),
+                c if c.is_numeric() && i > 0 => (),
+                _ => return None,
+            }
+        }
+        None
+    }
+
+    // Moves the position to the character at `byte_offset`
+    fn move_to_byte_offset(&mut self, byte_offset: usize) {
+        self.pos = match self
+            .char_indices
+            .binary_search_by_key(&byte_offset, |&(offset, _)| offset)
+        {
+            Ok(pos) => pos,
+            Err(_) => self.len,
+        };
+    }
+
+    fn diagnostic(&mut self, kind: DiagnosticKind, position: usize) {
+        self.diagnostics.push(LexDiagnostic { kind, position });
+    }
+
+    /// Lex the buffer, returning what was lexed so far if one of the
+    /// limits was exceeded.
+    pub fn lex(self) -> Sql {
+        match self.try_lex() {
+            Ok(sql) => sql,
+            Err(error) => error.into_partial(),
+        }
+    }
+
+    pub fn try_lex(mut self) -> Result<Sql, LexError> {
+        let tokens = self.lex_tokens();
+        let limits = self.limits;
+        let exceeded = self.exceeded;
+        let mut diagnostics = self.diagnostics;
+        diagnostics.sort_by_key(|diagnostic| diagnostic.position);
+        let sql = Sql {
+            buf: self.buf,
+            tokens,
+            diagnostics,
+        };
+
+        match exceeded {
+            None => Ok(sql),
+            Some(Exceeded::Bytes(len)) => Err(LexError::TooLong {
+                len,
+                max_bytes: limits.max_bytes,
+                partial: sql,
+            }),
+            Some(Exceeded::Tokens) => Err(LexError::TooManyTokens {
+                max_tokens: limits.max_tokens,
+                partial: sql,
+            }),
+            Some(Exceeded::Nesting) => Err(LexError::TooDeeplyNested {
+                max_nesting: limits.max_nesting,
+                partial: sql,
+            }),
+        }
+    }
+
     #[allow(clippy::match_like_matches_macro)]
-    pub fn lex(mut self) -> Sql {
+    fn lex_tokens(&mut self) -> Vec<Token> {
         let mut tokens = Vec::new();
+        // Opening parentheses and square brackets we haven't seen the
+        // closing counterpart of yet, with their position.
+        let mut open_delimiters: Vec<(char, usize)> = Vec::new();
 
         loop {
             if self.pos >= self.len {
                 break;
             }
 
+            if tokens.len() >= self.limits.max_tokens {
+                self.exceeded = Some(Exceeded::Tokens);
+                break;
+            }
+
             let (current_byte_offset, current_char) = self.char_indices[self.pos];
 
             let token = match current_char {
@@ -140,6 +253,10 @@ impl SqlLexer {
                             && lexer.char_at(lexer.pos - 2) == '*'
                             && lexer.char_at(lexer.pos - 1) == '/'
                     });
+                    let comment = &self.buf[current_byte_offset..end_byte_offset];
+                    if comment.len() < 4 || !comment.ends_with("*/") {
+                        self.diagnostic(DiagnosticKind::UnterminatedComment, current_byte_offset);
+                    }
                     Token::Comment(BufferSlice::new(current_byte_offset, end_byte_offset))
                 }
                 // Generic tokens
@@ -185,7 +302,24 @@ impl SqlLexer {
                 }
                 '?' => {
                     self.pos += 1;
-                    Token::Placeholder
+                    Token::Placeholder(Placeholder::Bound)
+                }
+                // Dollar quoted
+                '$' if self.dollar_quote_tag().is_some() => {
+                    let tag = self.dollar_quote_tag().unwrap_or("$").to_string();
+                    let body_byte_offset = current_byte_offset + tag.len();
+                    let end_byte_offset = match self.buf[body_byte_offset..].find(&tag) {
+                        Some(offset) => body_byte_offset + offset + tag.len(),
+                        None => {
+                            self.diagnostic(
+                                DiagnosticKind::UnterminatedQuote('$'),
+                                current_byte_offset,
+                            );
+                            self.buf.len()
+                        }
+                    };
+                    self.move_to_byte_offset(end_byte_offset);
+                    Token::DollarQuoted(BufferSlice::new(current_byte_offset, end_byte_offset))
                 }
                 '$' => {
                     let end_byte_offset =
@@ -260,7 +394,11 @@ impl SqlLexer {
                         // JSON
                         "#>" => Token::Operator(Operator::Json(JsonOperator::SpecifiedPath)),
                         "#>>" => Token::Operator(Operator::Json(JsonOperator::SpecifiedPathAsText)),
-                        _ => break,
+                        // We don't know how to continue from here
+                        _ => {
+                            self.diagnostic(DiagnosticKind::UnknownOperator, current_byte_offset);
+                            break;
+                        }
                     }
                 }
                 // Charset literal value type indicator
@@ -395,25 +533,55 @@ impl SqlLexer {
                 // Unknown
                 c => {
                     self.pos += 1;
+                    self.diagnostic(DiagnosticKind::UnknownCharacter(c), current_byte_offset);
                     Token::Unknown(c)
                 }
             };
 
+            match token {
+                Token::ParentheseOpen => open_delimiters.push(('(', current_byte_offset)),
+                Token::SquareBracketOpen => open_delimiters.push(('[', current_byte_offset)),
+                Token::ParentheseClose | Token::SquareBracketClose => {
+                    let (open, close) = match token {
+                        Token::ParentheseClose => ('(', ')'),
+                        _ => ('[', ']'),
+                    };
+                    match open_delimiters.last() {
+                        Some(&(c, _)) if c == open => {
+                            open_delimiters.pop();
+                        }
+                        _ => {
+                            self.diagnostic(DiagnosticKind::Unbalanced(close), current_byte_offset)
+                        }
+                    }
+                }
+                _ => (),
+            }
+            if open_delimiters.len() > self.limits.max_nesting {
+                self.exceeded = Some(Exceeded::Nesting);
+                break;
+            }
+
             tokens.push(token);
         }
 
-        Sql {
-            buf: self.buf,
-            tokens,
+        // Only complain about unclosed delimiters if we lexed everything
+        if self.exceeded.is_none() {
+            for (open, position) in open_delimiters {
+                self.diagnostic(DiagnosticKind::Unbalanced(open), position);
+            }
         }
+
+        tokens
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::super::{
-        ArithmeticOperator, BitwiseOperator, BufferSlice, ComparisonOperator, JsonOperator,
-        Keyword, LiteralValueTypeIndicator, LogicalOperator, Operator, Token,
+        ArithmeticOperator, BitwiseOperator, BufferSlice, ComparisonOperator, DiagnosticKind,
+        JsonOperator, Keyword, LexDiagnostic, LexError, Limits, LiteralValueTypeIndicator,
+        LogicalOperator, Operator, Placeholder, Token,
     };
     use super::SqlLexer;
 
@@ -1136,6 +1304,43 @@ mod tests {
         assert_eq!(lexer.lex().tokens, expected);
     }
 
+    #[test]
+    fn test_dollar_quoted() {
+        let sql = "$$val;ue$$ $body$sec$$ret$body$ $1".to_string();
+        let lexer = SqlLexer::new(sql);
+        let expected = vec![
+            Token::DollarQuoted(BufferSlice::new(0, 10)),
+            Token::Space,
+            Token::DollarQuoted(BufferSlice::new(11, 31)),
+            Token::Space,
+            Token::NumberedPlaceholder(BufferSlice::new(32, 34)),
+        ];
+
+        assert_eq!(lexer.lex().tokens, expected);
+    }
+
+    #[test]
+    fn test_dollar_quoted_missing_delimiter() {
+        let sql = "SELECT $tag$value".to_string();
+        let sql = SqlLexer::new(sql).lex();
+
+        assert_eq!(
+            sql.tokens,
+            vec![
+                Token::Keyword(Keyword::Select),
+                Token::Space,
+                Token::DollarQuoted(BufferSlice::new(7, 17)),
+            ]
+        );
+        assert_eq!(
+            sql.diagnostics,
+            vec![LexDiagnostic {
+                kind: DiagnosticKind::UnterminatedQuote('$'),
+                position: 7,
+            }]
+        );
+    }
+
     #[test]
     fn test_multibyte_characters() {
         let sql = "\"hæld\" ; 'jæld' ; `tæld`".to_string();
@@ -1163,7 +1368,7 @@ mod tests {
         let sql = "? $1 $2 $23;".to_string();
         let lexer = SqlLexer::new(sql);
         let expected = vec![
-            Token::Placeholder,
+            Token::Placeholder(Placeholder::Bound),
             Token::Space,
             Token::NumberedPlaceholder(BufferSlice::new(2, 4)),
             Token::Space,
@@ -1181,7 +1386,7 @@ mod tests {
         let sql = "? $1 $2 $23".to_string();
         let lexer = SqlLexer::new(sql);
         let expected = vec![
-            Token::Placeholder,
+            Token::Placeholder(Placeholder::Bound),
             Token::Space,
             Token::NumberedPlaceholder(BufferSlice::new(2, 4)),
             Token::Space,
@@ -1311,4 +1516,143 @@ mod tests {
 
         assert_eq!(lexer.lex().tokens, expected);
     }
+
+    #[test]
+    fn test_limits_max_bytes_multibyte_characters() {
+        // The limit falls in the middle of the two byte æ
+        let sql = "'hæld'".to_string();
+        let limits = Limits {
+            max_bytes: 3,
+            ..Limits::default()
+        };
+        let lexer = SqlLexer::with_limits(sql, limits);
+
+        match lexer.try_lex() {
+            Err(LexError::TooLong { len, partial, .. }) => {
+                assert_eq!(len, 7);
+                assert_eq!(
+                    partial.tokens,
+                    vec![Token::SingleQuoted(BufferSlice::new(1, 2))]
+                );
+            }
+            result => panic!("unexpected result {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_limits_lex_returns_partial() {
+        let sql = "SELECT * FROM table".to_string();
+        let limits = Limits {
+            max_tokens: 3,
+            ..Limits::default()
+        };
+        let lexer = SqlLexer::with_limits(sql, limits);
+        let expected = vec![
+            Token::Keyword(Keyword::Select),
+            Token::Space,
+            Token::Wildcard,
+        ];
+
+        assert_eq!(lexer.lex().tokens, expected);
+    }
+
+    #[test]
+    fn test_diagnostics_none() {
+        let sql = "SELECT * FROM \"table\" WHERE id IN (1, 2) /* comment */".to_string();
+        let lexer = SqlLexer::new(sql);
+
+        assert_eq!(lexer.lex().diagnostics, vec![]);
+    }
+
+    #[test]
+    fn test_diagnostics_unterminated_quote() {
+        let sql = "SELECT * FROM `table` WHERE name = 'secr".to_string();
+        let lexer = SqlLexer::new(sql);
+        let expected = vec![LexDiagnostic {
+            kind: DiagnosticKind::UnterminatedQuote('\''),
+            position: 35,
+        }];
+
+        assert_eq!(lexer.lex().diagnostics, expected);
+    }
+
+    #[test]
+    fn test_diagnostics_unterminated_comment() {
+        let sql = "SELECT * FROM table /* This is a comm".to_string();
+        let lexer = SqlLexer::new(sql);
+        let expected = vec![LexDiagnostic {
+            kind: DiagnosticKind::UnterminatedComment,
+            position: 20,
+        }];
+
+        assert_eq!(lexer.lex().diagnostics, expected);
+    }
+
+    #[test]
+    fn test_diagnostics_unknown_character() {
+        let sql = "SELECT ~ ^".to_string();
+        let lexer = SqlLexer::new(sql);
+        let expected = vec![
+            LexDiagnostic {
+                kind: DiagnosticKind::UnknownCharacter('~'),
+                position: 7,
+            },
+            LexDiagnostic {
+                kind: DiagnosticKind::UnknownCharacter('^'),
+                position: 9,
+            },
+        ];
+
+        assert_eq!(lexer.lex().diagnostics, expected);
+    }
+
+    #[test]
+    fn test_diagnostics_unknown_operator() {
+        let sql = "SELECT 1 =! 2".to_string();
+        let lexer = SqlLexer::new(sql);
+        let expected = vec![LexDiagnostic {
+            kind: DiagnosticKind::UnknownOperator,
+            position: 9,
+        }];
+
+        assert_eq!(lexer.lex().diagnostics, expected);
+    }
+
+    #[test]
+    fn test_diagnostics_unbalanced() {
+        let sql = "SELECT (a, [b) FROM t WHERE id IN (1, 2, 3".to_string();
+        let lexer = SqlLexer::new(sql);
+        let expected = vec![
+            LexDiagnostic {
+                kind: DiagnosticKind::Unbalanced('('),
+                position: 7,
+            },
+            LexDiagnostic {
+                kind: DiagnosticKind::Unbalanced('['),
+                position: 11,
+            },
+            LexDiagnostic {
+                kind: DiagnosticKind::Unbalanced(')'),
+                position: 13,
+            },
+            LexDiagnostic {
+                kind: DiagnosticKind::Unbalanced('('),
+                position: 34,
+            },
+        ];
+
+        assert_eq!(lexer.lex().diagnostics, expected);
+    }
+
+    #[test]
+    fn test_diagnostics_unbalanced_close() {
+        let sql = "SELECT a) FROM t".to_string();
+        let lexer = SqlLexer::new(sql);
+        let expected = vec![LexDiagnostic {
+            kind: DiagnosticKind::Unbalanced(')'),
+            position: 8,
+        }];
+
+        assert_eq!(lexer.lex().diagnostics, expected);
+    }
 }
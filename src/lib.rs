@@ -1,8 +1,28 @@
+use std::error::Error;
+use std::fmt;
+
+mod columns;
+mod functions;
 mod lexer;
+mod metrics;
+mod placeholders;
 mod sanitizer;
+mod statements;
+mod structure;
+mod tree;
+mod verify;
 mod writer;
 
-#[derive(Debug, PartialEq)]
+pub use columns::{Clause, ColumnOperator, ColumnUsage};
+pub use functions::FunctionCall;
+pub use metrics::QueryMetrics;
+pub use placeholders::PlaceholderCounts;
+pub use statements::{DdlKind, StatementKind, StatementRange, TransactionKind};
+pub use structure::{Cte, QueryStructure, Scope};
+pub use tree::{Delimiter, Group, TokenTree};
+pub use verify::{LeakKind, LeakReport};
+
+#[derive(Debug, Clone, PartialEq)]
 pub enum Keyword {
     Select,  // SELECT
     From,    // FROM
@@ -24,7 +44,7 @@ pub enum Keyword {
     Other(BufferSlice),
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Operator {
     Arithmetic(ArithmeticOperator),
     Logical(LogicalOperator),
@@ -33,7 +53,7 @@ pub enum Operator {
     Json(JsonOperator),
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum ArithmeticOperator {
     Multiply, // *
     Divide,   // /
@@ -42,7 +62,7 @@ pub enum ArithmeticOperator {
     Minus,    // -
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum LogicalOperator {
     In,     // IN
     Not,    // NOT
@@ -56,7 +76,7 @@ pub enum LogicalOperator {
     Else,   // ELSE
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum ComparisonOperator {
     Equal,              // =
     Equal2,             // ==
@@ -71,7 +91,7 @@ pub enum ComparisonOperator {
     LessThan,           // <
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum BitwiseOperator {
     LeftShift,  // <<
     RightShift, // >>
@@ -79,13 +99,13 @@ pub enum BitwiseOperator {
     Or,         // |
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum JsonOperator {
     SpecifiedPath,       // #>
     SpecifiedPathAsText, // #>>
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum LiteralValueTypeIndicator {
     Binary,               // BINARY
     Date,                 // DATE
@@ -99,7 +119,50 @@ pub enum LiteralValueTypeIndicator {
     Charset(BufferSlice), // Character set
 }
 
-#[derive(Debug, PartialEq)]
+/// A placeholder for a value. Bound placeholders show that the application
+/// used bind parameters, redacted ones that it put values in the query.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Placeholder {
+    /// A `?` bind parameter that was in the query
+    Bound,
+    /// A value that was replaced by the sanitizer
+    Redacted(ValueKind),
+}
+
+/// The kind of value the sanitizer replaced with a placeholder
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ValueKind {
+    String,  // 'value'
+    Number,  // 1, 1.5
+    Null,    // NULL
+    Boolean, // TRUE, FALSE
+    Date,    // DATE '2024-01-01', TIME '...', TIMESTAMP '...'
+    Hex,     // X'1F', 0x1F
+    Unknown, // A value that was cut off
+}
+
+/// Order of magnitude of the number of values in a list the sanitizer
+/// collapsed, see `SanitizeOptions::bucket_lists`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ListBucket {
+    One,             // 1
+    UpToTen,         // 2 to 10
+    UpToHundred,     // 11 to 100
+    MoreThanHundred, // More than 100
+}
+
+impl ListBucket {
+    pub fn for_count(count: usize) -> ListBucket {
+        match count {
+            0 | 1 => ListBucket::One,
+            2..=10 => ListBucket::UpToTen,
+            11..=100 => ListBucket::UpToHundred,
+            _ => ListBucket::MoreThanHundred,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct BufferSlice {
     pub start: usize,
     pub end: usize,
@@ -111,7 +174,7 @@ impl BufferSlice {
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Token {
     Operator(Operator),
     Keyword(Keyword),
@@ -119,6 +182,8 @@ pub enum Token {
     Backticked(BufferSlice),
     DoubleQuoted(BufferSlice),
     SingleQuoted(BufferSlice),
+    // Dollar quoted string including the tags, such as `$$value$$`
+    DollarQuoted(BufferSlice),
     Numeric(BufferSlice),
     Comment(BufferSlice),
     Space,
@@ -132,12 +197,26 @@ pub enum Token {
     SquareBracketClose,
     Colon,
     Semicolon,
-    // Used by the sanitizer to replace values
-    Placeholder,
-    // Used by the sanitizer to replace repeated value lists
-    Ellipsis,
+    // A bind parameter, or a value replaced by the sanitizer
+    Placeholder(Placeholder),
+    // Used by the sanitizer to replace a list of values, with the first
+    // value and the number of bound parameters and values in the list
+    PlaceholderList(Placeholder, PlaceholderCounts),
+    // Used by the sanitizer to replace a list of values when lists are
+    // bucketed by their size, with the number of bound parameters and
+    // values in the list
+    PlaceholderBucket(Placeholder, ListBucket, PlaceholderCounts),
+    // Used by the sanitizer to replace repeated value lists, with the
+    // number of value lists that were replaced and the number of bound
+    // parameters and values in them
+    Ellipsis(usize, PlaceholderCounts),
+    // Used by the sanitizer for the body of a function or DO block that
+    // was sanitized on its own, with the opening tag such as `$body$`
+    DollarQuotedBody(BufferSlice, Box<Sql>),
     // Used by the sanitizer to remove tokens
     None,
+    // Used by the sanitizer to mark a query that was cut off
+    Truncated,
     Null,
     True,
     False,
@@ -145,10 +224,36 @@ pub enum Token {
     Unknown(char),
 }
 
-#[derive(Debug, PartialEq)]
+/// Something that looked off while lexing, for example a quote that is
+/// never closed. This often means the query was truncated.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LexDiagnostic {
+    pub kind: DiagnosticKind,
+    /// Byte offset in the buffer where the problem starts
+    pub position: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DiagnosticKind {
+    /// A quoted value or identifier without a closing quote
+    UnterminatedQuote(char),
+    /// A multi line comment without a closing `*/`
+    UnterminatedComment,
+    /// A character the lexer doesn't know
+    UnknownCharacter(char),
+    /// A combination of operator characters the lexer doesn't know,
+    /// lexing stops here.
+    UnknownOperator,
+    /// A parenthese or square bracket that is never closed, or a closing
+    /// one that was never opened.
+    Unbalanced(char),
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct Sql {
     buf: String,
     pub tokens: Vec<Token>,
+    pub diagnostics: Vec<LexDiagnostic>,
 }
 
 impl Sql {
@@ -160,39 +265,347 @@ impl Sql {
         }
         &self.buf[pos.start..pos.end]
     }
+
+    // Whether `token` is an other keyword that matches `keyword`, ignoring case.
+    pub(crate) fn is_keyword(&self, token: &Token, keyword: &str) -> bool {
+        match token {
+            Token::Keyword(Keyword::Other(slice)) => {
+                self.buffer_content(slice).eq_ignore_ascii_case(keyword)
+            }
+            _ => false,
+        }
+    }
 }
 
+/// Limits on the work done when lexing untrusted input, see `try_lex`
+/// and `try_sanitize`. The default has no limits at all.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Limits {
+    /// Maximum length of the input in bytes
+    pub max_bytes: usize,
+    /// Maximum number of tokens
+    pub max_tokens: usize,
+    /// Maximum depth of nested parentheses and square brackets
+    pub max_nesting: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Limits {
+        Limits {
+            max_bytes: usize::MAX,
+            max_tokens: usize::MAX,
+            max_nesting: usize::MAX,
+        }
+    }
+}
+
+/// Returned when lexing stopped because one of the `Limits` was exceeded.
+/// Every variant contains the part of the query that was lexed before
+/// stopping.
+#[derive(Debug, PartialEq)]
+pub enum LexError {
+    TooLong {
+        len: usize,
+        max_bytes: usize,
+        partial: Sql,
+    },
+    TooManyTokens {
+        max_tokens: usize,
+        partial: Sql,
+    },
+    TooDeeplyNested {
+        max_nesting: usize,
+        partial: Sql,
+    },
+}
+
+impl LexError {
+    /// The part of the query that was lexed before stopping.
+    pub fn partial(&self) -> &Sql {
+        match self {
+            LexError::TooLong { partial, .. }
+            | LexError::TooManyTokens { partial, .. }
+            | LexError::TooDeeplyNested { partial, .. } => partial,
+        }
+    }
+
+    pub fn into_partial(self) -> Sql {
+        match self {
+            LexError::TooLong { partial, .. }
+            | LexError::TooManyTokens { partial, .. }
+            | LexError::TooDeeplyNested { partial, .. } => partial,
+        }
+    }
+
+    fn map_partial<F>(self, f: F) -> LexError
+    where
+        F: FnOnce(Sql) -> Sql,
+    {
+        match self {
+            LexError::TooLong {
+                len,
+                max_bytes,
+                partial,
+            } => LexError::TooLong {
+                len,
+                max_bytes,
+                partial: f(partial),
+            },
+            LexError::TooManyTokens {
+                max_tokens,
+                partial,
+            } => LexError::TooManyTokens {
+                max_tokens,
+                partial: f(partial),
+            },
+            LexError::TooDeeplyNested {
+                max_nesting,
+                partial,
+            } => LexError::TooDeeplyNested {
+                max_nesting,
+                partial: f(partial),
+            },
+        }
+    }
+}
+
+impl fmt::Display for LexError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LexError::TooLong { len, max_bytes, .. } => write!(
+                f,
+                "query of {} bytes is longer than the limit of {} bytes",
+                len, max_bytes
+            ),
+            LexError::TooManyTokens { max_tokens, .. } => {
+                write!(f, "query has more than {} tokens", max_tokens)
+            }
+            LexError::TooDeeplyNested { max_nesting, .. } => {
+                write!(f, "query is nested deeper than {} levels", max_nesting)
+            }
+        }
+    }
+}
+
+impl Error for LexError {}
+
 /// Lex a sql string into a `Sql` struct that contains the original
 /// buffer and the tokens found.
 pub fn lex(buf: String) -> Sql {
     lexer::SqlLexer::new(buf).lex()
 }
 
+/// Lex a sql string, stopping as soon as one of the `limits` is exceeded.
+pub fn try_lex(buf: String, limits: &Limits) -> Result<Sql, LexError> {
+    lexer::SqlLexer::with_limits(buf, *limits).try_lex()
+}
+
 /// Write a `Sql` struct back to a sql string.
 pub fn write(sql: Sql) -> String {
     writer::SqlWriter::new(sql).write()
 }
 
+/// Options to change how a `Sql` struct is written, see
+/// `write_with_options`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct WriteOptions {
+    /// Add the number of values the sanitizer collapsed into a single
+    /// placeholder or ellipsis as a comment, for example `IN (? /*500*/)`.
+    pub show_collapsed_counts: bool,
+    /// How to write the placeholders the sanitizer inserted
+    pub placeholder_style: PlaceholderStyle,
+    /// Add the kind of value that was replaced to placeholders, such as
+    /// `?:str`, `?:num`, `?:null`, `?:bool`, `?:date` and `?:hex`. Makes a
+    /// string compared to a numeric column visible in the sanitized query.
+    pub typed_placeholders: bool,
+}
+
+/// Placeholder syntax of the different databases. The numbered styles
+/// continue after the highest placeholder of the same style in the query,
+/// so a query with `$1` and `$2` gets `$3` for the first inserted
+/// placeholder.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum PlaceholderStyle {
+    #[default]
+    QuestionMark, // ?
+    Dollar,  // $1, $2, ...
+    Colon,   // :1, :2, ...
+    AtP,     // @p1, @p2, ...
+    Percent, // %s
+}
+
+/// Write a `Sql` struct back to a sql string with non-default options
+pub fn write_with_options(sql: Sql, options: &WriteOptions) -> String {
+    writer::SqlWriter::with_options(sql, options.clone()).write()
+}
+
+/// Write a `Sql` struct back to a sql string of at most `max_len` bytes.
+/// If it doesn't fit it's cut off at a token boundary, open parentheses
+/// and square brackets are closed and `...TRUNCATED` is appended.
+pub fn write_truncated(sql: Sql, max_len: usize) -> String {
+    writer::SqlWriter::new(sql).write_truncated(max_len)
+}
+
+/// Options to change how the sanitizer behaves, see `sanitize_with_options`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SanitizeOptions {
+    /// Recognise queries that were cut off, for example by a driver or
+    /// `pg_stat_activity`. An unterminated value is replaced with a
+    /// placeholder, open parentheses and square brackets are closed and
+    /// the query is marked with `...TRUNCATED`.
+    pub detect_truncation: bool,
+    /// Collapse lists of values such as `IN (1, 2, 3)` into a number of
+    /// placeholders that depends on the size of the list: `IN (?)` for
+    /// one value, `IN (?, ?+)` for up to 10, `IN (?, ?, ?+)` for up to 100
+    /// and `IN (?, ?, ?, ?+)` for more. Queries with small and huge lists
+    /// then end up in different groups.
+    pub bucket_lists: bool,
+    /// Replace every quoted, dollar quoted, numeric and hexadecimal value,
+    /// no matter where it is in the query. Values the sanitizer normally
+    /// leaves alone, for example in a select list or in `THEN 'value'`, are
+    /// replaced too.
+    pub paranoid: bool,
+    /// Keywords after which numbers are kept in paranoid mode, for example
+    /// `LIMIT`. Matched without regard to case. This doesn't keep numbers
+    /// the sanitizer replaces without paranoid mode.
+    pub numeric_allowlist: Vec<String>,
+    /// Also replace the row counts in `LIMIT 10`, `FETCH FIRST 10 ROWS` and
+    /// `TOP 10`. These are kept by default, so queries that fetch a
+    /// different number of rows are grouped separately. Other numbers that
+    /// shape the result are always replaced, such as window frame sizes in
+    /// `ROWS 5 PRECEDING`, sample sizes in `TABLESAMPLE BERNOULLI (5)`,
+    /// counts in parentheses as in `TOP (10)`, offsets and `REPEATABLE`
+    /// seeds.
+    pub redact_structural_numbers: bool,
+    /// Sanitize the bodies of functions and DO blocks, as in `AS $$ ... $$`
+    /// and `DO $$ ... $$`, as queries of their own with the same options.
+    /// The body keeps its structure instead of being replaced with one
+    /// placeholder. Bodies nested more than eight levels deep are still
+    /// replaced as a whole.
+    pub sanitize_bodies: bool,
+}
+
+/// Group the tokens of a `Sql` struct by matching parentheses and square
+/// brackets.
+pub fn token_tree(sql: &Sql) -> TokenTree {
+    TokenTree::new(sql)
+}
+
 /// Sanitize a `Sql` struct
 pub fn sanitize(sql: Sql) -> Sql {
     sanitizer::SqlSanitizer::new(sql).sanitize()
 }
 
+/// Sanitize a `Sql` struct with non-default options
+pub fn sanitize_with_options(sql: Sql, options: &SanitizeOptions) -> Sql {
+    sanitizer::SqlSanitizer::with_options(sql, options.clone()).sanitize()
+}
+
 /// Returns a sanitized sql string
 pub fn sanitize_string(buf: String) -> String {
     write(sanitize(lex(buf)))
 }
 
+/// Split a `Sql` struct that contains a script with multiple statements
+/// into the ranges of tokens of each statement. Semicolons within
+/// `BEGIN ... END` blocks don't end a statement.
+pub fn split_statements(sql: &Sql) -> Vec<StatementRange> {
+    statements::split(sql)
+}
+
+/// Classify the (first) statement in a `Sql` struct by its leading
+/// keywords, see `StatementRange::kind` to classify every statement in a
+/// script.
+pub fn statement_kind(sql: &Sql) -> StatementKind {
+    statements::kind(sql, 0, sql.tokens.len())
+}
+
+/// Columns that are referenced in the `WHERE`, `JOIN ... ON`, `ORDER BY`
+/// and `GROUP BY` clauses of every statement in a `Sql` struct, with the
+/// operator they are compared with. Useful to suggest indexes.
+pub fn columns(sql: &Sql) -> Vec<Vec<ColumnUsage>> {
+    columns::columns(sql)
+}
+
+/// Calls to functions in a `Sql` struct in the order they appear in,
+/// nested calls are included.
+pub fn functions(sql: &Sql) -> Vec<FunctionCall> {
+    functions::functions(sql)
+}
+
+/// The common table expressions, subqueries and the tables they reference
+/// of every statement in a `Sql` struct.
+pub fn structure(sql: &Sql) -> QueryStructure {
+    structure::structure(sql)
+}
+
+/// Counts of joins, subqueries, `IN` list items and other things that make
+/// a query expensive. Pass the `Sql` struct before it is sanitized, the
+/// sanitizer collapses lists.
+pub fn metrics(sql: &Sql) -> QueryMetrics {
+    metrics::metrics(sql)
+}
+
+/// Count the bind parameters and the values the sanitizer replaced in a
+/// sanitized `Sql` struct. Redacted values mean the application put values
+/// in the query instead of using prepared statements.
+pub fn placeholder_counts(sql: &Sql) -> PlaceholderCounts {
+    placeholders::counts(sql)
+}
+
+/// Check that the written output of a sanitized `Sql` struct contains no
+/// values or comments. The output is lexed again, so this is a second line
+/// of defence against values the sanitizer missed.
+pub fn verify_sanitized(sql: &Sql) -> Result<(), Vec<LeakReport>> {
+    verify::verify_sanitized(sql)
+}
+
+/// Returns a sanitized sql string for every statement in a script with
+/// multiple statements. Each statement is sanitized on its own, without the
+/// semicolon that separates it from the next one.
+pub fn sanitize_each(buf: String) -> Vec<String> {
+    let sql = lex(buf);
+    let ranges = split_statements(&sql);
+    // The sanitizer starts over at every semicolon, and keeps the positions
+    // of the tokens the same, so the statements can be sanitized at once.
+    let writer = writer::SqlWriter::new(sanitize(sql));
+
+    ranges
+        .iter()
+        .filter_map(|range| {
+            let sanitized = writer.write_range(range);
+            let sanitized = sanitized.trim();
+            if sanitized.is_empty() {
+                None
+            } else {
+                Some(sanitized.to_string())
+            }
+        })
+        .collect()
+}
+
+/// Lex and sanitize a sql string, stopping as soon as one of the `limits`
+/// is exceeded. The partial query in the error is sanitized as well.
+pub fn try_sanitize(buf: String, limits: &Limits) -> Result<Sql, LexError> {
+    match try_lex(buf, limits) {
+        Ok(sql) => Ok(sanitize(sql)),
+        Err(error) => Err(error.map_partial(sanitize)),
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use super::Sql;
-    use super::{BufferSlice, ComparisonOperator, Keyword, Operator, Token};
+    use super::{
+        BufferSlice, ComparisonOperator, Keyword, Operator, Placeholder, Token, ValueKind,
+    };
+    use super::{LexError, Limits, Sql};
 
     #[test]
     fn test_buffer_content() {
         let sql = Sql {
             buf: "SELECT `table`.* FROM `table` WHERE `id` = 'secret';".to_string(),
             tokens: Vec::new(),
+            diagnostics: Vec::new(),
         };
         let buffer_position = BufferSlice::new(17, 21);
 
@@ -204,6 +617,7 @@ mod tests {
         let sql = Sql {
             buf: "\"hæld\" ; 'jæld' ; `tæld`".to_string(),
             tokens: Vec::new(),
+            diagnostics: Vec::new(),
         };
 
         assert_eq!("hæld", sql.buffer_content(&BufferSlice::new(1, 6)));
@@ -216,6 +630,7 @@ mod tests {
         let sql = Sql {
             buf: "buffer content".to_string(),
             tokens: Vec::new(),
+            diagnostics: Vec::new(),
         };
         let buffer_position = BufferSlice::new(6, 1);
 
@@ -227,6 +642,7 @@ mod tests {
         let sql = Sql {
             buf: "buffer content".to_string(),
             tokens: Vec::new(),
+            diagnostics: Vec::new(),
         };
         let buffer_position = BufferSlice::new(100, 200);
 
@@ -238,6 +654,7 @@ mod tests {
         let sql = Sql {
             buf: "buffer content".to_string(),
             tokens: Vec::new(),
+            diagnostics: Vec::new(),
         };
         let buffer_position = BufferSlice::new(0, 200);
 
@@ -290,7 +707,7 @@ mod tests {
             Token::Space,
             Token::Operator(Operator::Comparison(ComparisonOperator::Equal)),
             Token::Space,
-            Token::Placeholder,
+            Token::Placeholder(Placeholder::Redacted(ValueKind::Number)),
             Token::Semicolon,
         ];
 
@@ -304,4 +721,90 @@ mod tests {
             "SELECT * FROM `table` WHERE id = ?;"
         );
     }
+
+    #[test]
+    fn test_try_lex_within_limits() {
+        let limits = Limits {
+            max_bytes: 100,
+            max_tokens: 100,
+            max_nesting: 2,
+        };
+        let sql = super::try_lex("SELECT * FROM `table`".to_string(), &limits).unwrap();
+
+        assert_eq!(sql, super::lex("SELECT * FROM `table`".to_string()));
+    }
+
+    #[test]
+    fn test_try_sanitize_within_limits() {
+        let sql = super::try_sanitize(
+            "SELECT * FROM `table` WHERE id = 1;".to_string(),
+            &Limits::default(),
+        )
+        .unwrap();
+
+        assert_eq!(super::write(sql), "SELECT * FROM `table` WHERE id = ?;");
+    }
+
+    #[test]
+    fn test_try_sanitize_too_long() {
+        let limits = Limits {
+            max_bytes: 32,
+            ..Limits::default()
+        };
+        let error = super::try_sanitize(
+            "SELECT * FROM `table` WHERE id = 1 AND name = 'secret';".to_string(),
+            &limits,
+        )
+        .unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            "query of 55 bytes is longer than the limit of 32 bytes"
+        );
+        assert_eq!(
+            super::write(error.into_partial()),
+            "SELECT * FROM `table` WHERE id ="
+        );
+    }
+
+    #[test]
+    fn test_try_sanitize_too_many_tokens() {
+        let limits = Limits {
+            max_tokens: 15,
+            ..Limits::default()
+        };
+        let error = super::try_sanitize(
+            "SELECT * FROM `table` WHERE id = 1 AND name = 'secret';".to_string(),
+            &limits,
+        )
+        .unwrap_err();
+
+        match error {
+            LexError::TooManyTokens { max_tokens, .. } => assert_eq!(max_tokens, 15),
+            _ => panic!("unexpected error {:?}", error),
+        }
+        assert_eq!(
+            super::write(error.into_partial()),
+            "SELECT * FROM `table` WHERE id = ?"
+        );
+    }
+
+    #[test]
+    fn test_try_sanitize_too_deeply_nested() {
+        let limits = Limits {
+            max_nesting: 2,
+            ..Limits::default()
+        };
+        let error = super::try_sanitize(
+            "SELECT * FROM `table` WHERE id IN (SELECT id FROM (SELECT (((1))))))".to_string(),
+            &limits,
+        )
+        .unwrap_err();
+
+        assert_eq!(error.to_string(), "query is nested deeper than 2 levels");
+        assert_eq!(
+            super::write(error.into_partial()),
+            "SELECT * FROM `table` WHERE id IN (SELECT id FROM (SELECT "
+        );
+    }
 }
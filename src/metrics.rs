@@ -0,0 +1,170 @@
+use super::functions::arg_count;
+use super::statements::is_significant;
+use super::structure::structure;
+use super::{Keyword, LogicalOperator, Operator, Sql, Token, TokenTree};
+
+/// Counts that indicate how expensive a query might be, taken from the
+/// query before it is sanitized.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct QueryMetrics {
+    pub joins: usize,
+    /// Subqueries, not counting the bodies of common table expressions
+    pub subqueries: usize,
+    pub ctes: usize,
+    pub or_conditions: usize,
+    /// Number of items in every `IN (...)` list, in the order they appear in
+    pub in_list_sizes: Vec<usize>,
+    /// Number of rows in every `VALUES` clause, in the order they appear in
+    pub values_rows: Vec<usize>,
+    /// Deepest nesting of parentheses and square brackets
+    pub nesting_depth: usize,
+    pub total_tokens: usize,
+}
+
+pub fn metrics(sql: &Sql) -> QueryMetrics {
+    let tree = TokenTree::new(sql);
+    let structure = structure(sql);
+    let positions: Vec<usize> = (0..sql.tokens.len())
+        .filter(|pos| is_significant(&sql.tokens[*pos]))
+        .collect();
+
+    // The body of a common table expression is the first scope in it. The
+    // body can be missing if it's empty or the query was cut off.
+    let bodies: Vec<usize> = structure
+        .ctes
+        .iter()
+        .filter_map(|cte| {
+            structure
+                .scopes
+                .iter()
+                .position(|scope| cte.span.contains(&scope.span.start))
+        })
+        .collect();
+
+    let mut metrics = QueryMetrics {
+        subqueries: structure
+            .scopes
+            .iter()
+            .enumerate()
+            .filter(|(i, scope)| scope.depth > 0 && !bodies.contains(i))
+            .count(),
+        ctes: structure.ctes.len(),
+        nesting_depth: tree.max_depth(),
+        total_tokens: sql.tokens.len(),
+        ..QueryMetrics::default()
+    };
+
+    for (i, pos) in positions.iter().enumerate() {
+        match &sql.tokens[*pos] {
+            Token::Keyword(Keyword::Join) => metrics.joins += 1,
+            Token::Keyword(Keyword::Or) => metrics.or_conditions += 1,
+            Token::Operator(Operator::Logical(LogicalOperator::In)) => {
+                let open = match positions.get(i + 1) {
+                    Some(open) if sql.tokens[*open] == Token::ParentheseOpen => *open,
+                    _ => continue,
+                };
+                // IN (SELECT ...) is a subquery
+                if let Some(Token::Keyword(Keyword::Select)) =
+                    positions.get(i + 2).map(|pos| &sql.tokens[*pos])
+                {
+                    continue;
+                }
+                let close = tree.closing(open).unwrap_or(sql.tokens.len() - 1);
+                metrics
+                    .in_list_sizes
+                    .push(arg_count(sql, &tree, open, close));
+            }
+            Token::Keyword(Keyword::Values) => {
+                let mut rows = 0;
+                let mut next = i + 1;
+                while let Some(open) = positions.get(next) {
+                    if sql.tokens[*open] != Token::ParentheseOpen {
+                        break;
+                    }
+                    rows += 1;
+                    let close = match tree.closing(*open) {
+                        Some(close) => close,
+                        None => break,
+                    };
+                    next = positions.partition_point(|pos| *pos <= close);
+                    match positions.get(next) {
+                        Some(comma) if sql.tokens[*comma] == Token::Comma => next += 1,
+                        _ => break,
+                    }
+                }
+                metrics.values_rows.push(rows);
+            }
+            _ => (),
+        }
+    }
+
+    metrics
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{lex, metrics};
+    use super::QueryMetrics;
+
+    #[test]
+    fn test_select() {
+        let sql = lex(
+            "WITH a AS (SELECT * FROM x) SELECT * FROM a JOIN b ON a.id = b.id LEFT JOIN c ON c.id = b.id WHERE a.id IN (1, 2, 3) OR b.id IN (SELECT id FROM d WHERE e IN (4)) OR c.id = 5".to_string(),
+        );
+
+        assert_eq!(
+            metrics(&sql),
+            QueryMetrics {
+                joins: 2,
+                subqueries: 1,
+                ctes: 1,
+                or_conditions: 2,
+                in_list_sizes: vec![3, 1],
+                values_rows: vec![],
+                nesting_depth: 2,
+                total_tokens: sql.tokens.len(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_insert_values() {
+        let sql = lex(
+            "INSERT INTO t (a, b) VALUES (1, 'a'), (2, 'b'), (3, 'c'); INSERT INTO t (a) VALUES (LOWER('x'))"
+                .to_string(),
+        );
+        let metrics = metrics(&sql);
+
+        assert_eq!(metrics.values_rows, vec![3, 1]);
+        assert_eq!(metrics.nesting_depth, 2);
+        assert_eq!(metrics.subqueries, 0);
+    }
+
+    #[test]
+    fn test_cte_without_body() {
+        let metrics = metrics(&lex("WITH a AS () SELECT 1".to_string()));
+
+        assert_eq!(metrics.ctes, 1);
+        assert_eq!(metrics.subqueries, 0);
+    }
+
+    #[test]
+    fn test_truncated_cte() {
+        let metrics = metrics(&lex(
+            "WITH a AS (SELECT 1 FROM (SELECT 2) x), b AS (".to_string()
+        ));
+
+        assert_eq!(metrics.ctes, 2);
+        assert_eq!(metrics.subqueries, 1);
+
+        let metrics = super::metrics(&lex("WITH a AS (SELECT 1), b AS (".to_string()));
+
+        assert_eq!(metrics.ctes, 2);
+        assert_eq!(metrics.subqueries, 0);
+    }
+
+    #[test]
+    fn test_empty() {
+        assert_eq!(metrics(&lex("".to_string())), QueryMetrics::default());
+    }
+}
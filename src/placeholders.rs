@@ -0,0 +1,147 @@
+use super::{Placeholder, Sql, Token};
+
+/// The number of values in a sanitized query that were bind parameters
+/// and the number of values the sanitizer replaced.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct PlaceholderCounts {
+    pub bound: usize,
+    pub redacted: usize,
+}
+
+impl PlaceholderCounts {
+    /// The number of bound parameters and replaced values together
+    pub fn total(&self) -> usize {
+        self.bound + self.redacted
+    }
+
+    pub(crate) fn add(&mut self, other: PlaceholderCounts) {
+        self.bound += other.bound;
+        self.redacted += other.redacted;
+    }
+}
+
+pub fn counts(sql: &Sql) -> PlaceholderCounts {
+    let mut counts = PlaceholderCounts::default();
+    for token in sql.tokens.iter() {
+        let placeholder = match token {
+            Token::Placeholder(placeholder) => *placeholder,
+            Token::NumberedPlaceholder(_) => Placeholder::Bound,
+            Token::PlaceholderList(_, list)
+            | Token::PlaceholderBucket(_, _, list)
+            | Token::Ellipsis(_, list) => {
+                counts.add(*list);
+                continue;
+            }
+            Token::DollarQuotedBody(_, body) => {
+                counts.add(self::counts(body));
+                continue;
+            }
+            _ => continue,
+        };
+        match placeholder {
+            Placeholder::Bound => counts.bound += 1,
+            Placeholder::Redacted(_) => counts.redacted += 1,
+        }
+    }
+    counts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{lex, placeholder_counts, sanitize, sanitize_with_options, SanitizeOptions};
+    use super::PlaceholderCounts;
+
+    #[test]
+    fn test_counts() {
+        let sql = sanitize(lex(
+            "SELECT * FROM t WHERE a = ? AND b = $1 AND c = 'x' AND d IN ($2, $3) AND e IN (1, 2, 3)"
+                .to_string(),
+        ));
+
+        assert_eq!(
+            placeholder_counts(&sql),
+            PlaceholderCounts {
+                bound: 4,
+                redacted: 4,
+            }
+        );
+    }
+
+    #[test]
+    fn test_counts_in_collapsed_rows() {
+        let sql = sanitize(lex(
+            "INSERT INTO t (a, b) VALUES (1, $1), (2, $2), (3, 'c')".to_string(),
+        ));
+
+        assert_eq!(
+            placeholder_counts(&sql),
+            PlaceholderCounts {
+                bound: 2,
+                redacted: 4,
+            }
+        );
+    }
+
+    #[test]
+    fn test_counts_in_mixed_list() {
+        let sql = sanitize(lex(
+            "SELECT * FROM t WHERE a IN ($1, 'x') AND b IN (1, $2, ?, 'y')".to_string(),
+        ));
+
+        assert_eq!(
+            placeholder_counts(&sql),
+            PlaceholderCounts {
+                bound: 3,
+                redacted: 3,
+            }
+        );
+    }
+
+    #[test]
+    fn test_counts_in_bucketed_list() {
+        let options = SanitizeOptions {
+            bucket_lists: true,
+            ..Default::default()
+        };
+        let sql = sanitize_with_options(
+            lex("SELECT * FROM t WHERE a IN (1, 2, 3, 4, 5) AND b IN ($1, 'x')".to_string()),
+            &options,
+        );
+
+        assert_eq!(
+            placeholder_counts(&sql),
+            PlaceholderCounts {
+                bound: 1,
+                redacted: 6,
+            }
+        );
+    }
+
+    #[test]
+    fn test_counts_in_bodies() {
+        let options = SanitizeOptions {
+            sanitize_bodies: true,
+            ..Default::default()
+        };
+        let sql = sanitize_with_options(
+            lex("DO $$ BEGIN UPDATE t SET a = 'x', b = $1 WHERE c = 1; END $$".to_string()),
+            &options,
+        );
+
+        assert_eq!(
+            placeholder_counts(&sql),
+            PlaceholderCounts {
+                bound: 1,
+                redacted: 2,
+            }
+        );
+    }
+
+    #[test]
+    fn test_counts_without_placeholders() {
+        assert_eq!(
+            placeholder_counts(&sanitize(lex("SELECT * FROM t".to_string()))),
+            PlaceholderCounts::default()
+        );
+    }
+}
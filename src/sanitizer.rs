@@ -1,6 +1,57 @@
-use super::{Keyword, LogicalOperator, Operator, Sql, Token};
-
-#[derive(Debug, PartialEq)]
+use super::statements::is_significant;
+use super::{
+    lex, ArithmeticOperator, BufferSlice, ComparisonOperator, DiagnosticKind, Keyword, ListBucket,
+    LiteralValueTypeIndicator, LogicalOperator, Operator, Placeholder, PlaceholderCounts,
+    SanitizeOptions, Sql, Token, TokenTree, ValueKind,
+};
+
+// Type names that can be put in front of a quoted value, in addition to the
+// ones the lexer turns into a `LiteralValueTypeIndicator`. ZONE is the end
+// of `TIMESTAMP WITH TIME ZONE '...'`.
+const TYPE_PREFIXES: &[&str] = &["interval", "zone", "json", "jsonb", "uuid", "inet"];
+
+// Functions that convert a value to a type, as in `CAST('x' AS text)`
+const CASTS: &[&str] = &["cast", "try_cast", "safe_cast"];
+
+// Keywords in a COPY statement that are followed by a file path, command,
+// URL or connection
+const COPY_LOCATIONS: &[&str] = &["to", "program", "connection", "iam_role"];
+
+// Keywords that are followed by a file path in any statement, as in LOAD
+// DATA INFILE and SELECT ... INTO OUTFILE
+const FILE_LOCATIONS: &[&str] = &["infile", "outfile", "dumpfile"];
+
+// Keywords and option names that are followed by a password, secret or
+// key, as in `IDENTIFIED BY '...'`, `MASTER_PASSWORD = '...'` and
+// `CREATE SECRET (KEY_ID '...')`. Only whole names match, so columns such as
+// `api_key` or `tokens` are not mistaken for them.
+const CREDENTIALS: &[&str] = &[
+    "identified",
+    "password",
+    "passwd",
+    "secret",
+    "credential",
+    "credentials",
+    "master_password",
+    "source_password",
+    "key_id",
+    "secret_key",
+    "access_key_id",
+    "secret_access_key",
+    "aws_key_id",
+    "aws_secret_key",
+    "aws_token",
+    "session_token",
+    "sas_token",
+    "account_key",
+];
+
+// How deep bodies in bodies are sanitized on their own, deeper ones are
+// replaced as a whole so a query can't nest them deep enough to overflow
+// the stack.
+const MAX_BODY_DEPTH: usize = 8;
+
+#[derive(Clone, Debug, PartialEq)]
 enum State {
     Default,
     ComparisonOperator,
@@ -13,19 +64,41 @@ enum State {
     KeywordScopeStarted,
     Array,
     ArrayStarted,
+    SessionVariable,
+    Assignment,
+    StructuralNumber,
 }
 
 pub struct SqlSanitizer {
     pub sql: Sql,
+    options: SanitizeOptions,
+    // The number of bodies this query is in
+    body_depth: usize,
 }
 
 impl SqlSanitizer {
     pub fn new(sql: Sql) -> SqlSanitizer {
-        SqlSanitizer { sql }
+        SqlSanitizer::with_options(sql, SanitizeOptions::default())
+    }
+
+    pub fn with_options(sql: Sql, options: SanitizeOptions) -> SqlSanitizer {
+        SqlSanitizer {
+            sql,
+            options,
+            body_depth: 0,
+        }
     }
 
     pub fn sanitize(mut self) -> Sql {
+        self.join_numbers();
+        let tree = TokenTree::new(&self.sql);
         let mut state = State::Default;
+        // The state right after opening each of the groups we're in, so we
+        // can continue where we were when a nested group is closed.
+        let mut scopes: Vec<State> = Vec::new();
+        // The number of scopes we were in at the SET of an assignment list
+        // such as `UPDATE t SET a = 1, b = 2`.
+        let mut assignments: Option<usize> = None;
 
         let mut pos = 0;
         loop {
@@ -33,8 +106,97 @@ impl SqlSanitizer {
                 break;
             }
 
+            let token_pos = pos;
             let token = &self.sql.tokens[pos];
+            let opens_scope = matches!(token, Token::ParentheseOpen | Token::SquareBracketOpen);
+            let closes_scope = matches!(token, Token::ParentheseClose | Token::SquareBracketClose);
+            if assignments.is_some() && self.ends_assignments(token) {
+                assignments = None;
+            }
             match (token, &state) {
+                // SET at the start of a statement assigns session variables
+                // and ALTER SYSTEM SET server settings, every value up to the
+                // end of the statement is replaced.
+                (Token::Keyword(Keyword::Set), _)
+                    if self.starts_statement(pos)
+                        || self
+                            .previous_significant(pos)
+                            .is_some_and(|previous| self.sql.is_keyword(previous, "system")) =>
+                {
+                    state = State::SessionVariable
+                }
+                (
+                    Token::SingleQuoted(_)
+                    | Token::DollarQuoted(_)
+                    | Token::Numeric(_)
+                    | Token::Null
+                    | Token::True
+                    | Token::False,
+                    State::SessionVariable,
+                ) => self.placeholder(pos),
+                (Token::DoubleQuoted(_), State::SessionVariable) if !self.in_dotted_path(pos) => {
+                    self.placeholder(pos)
+                }
+                // Keep the state until the end of the statement
+                (token, State::SessionVariable)
+                    if !matches!(token, Token::Semicolon | Token::Comment(_)) => {}
+                // The body of a function or DO block is replaced as a whole,
+                // unless it's sanitized on its own
+                (Token::DollarQuoted(slice), _) if is_body(&self.sql, pos) => {
+                    if self.options.sanitize_bodies && self.body_depth < MAX_BODY_DEPTH {
+                        let slice = slice.clone();
+                        self.sanitize_body(pos, &slice)
+                    } else {
+                        self.placeholder(pos)
+                    }
+                }
+                // A value with a type, such as `DATE '2024-01-01'` or
+                // `'{1,2,3}'::int[]`, is always replaced and the type is kept.
+                // Within a list of values the whole list is replaced instead.
+                (Token::SingleQuoted(_) | Token::DollarQuoted(_) | Token::Numeric(_), state)
+                    if !matches!(state, State::ArrayStarted | State::KeywordScopeStarted)
+                        && self.is_typed_literal(pos) =>
+                {
+                    self.placeholder(pos)
+                }
+                // UPDATE ... SET, ON CONFLICT ... DO UPDATE SET, WHEN MATCHED THEN
+                // UPDATE SET and ON DUPLICATE KEY UPDATE start a list of assignments.
+                (Token::Keyword(Keyword::Set), _) => {
+                    state = State::Assignment;
+                    assignments = Some(scopes.len());
+                }
+                (Token::Keyword(Keyword::Update), _)
+                    if self
+                        .previous_significant(pos)
+                        .is_some_and(|previous| self.sql.is_keyword(previous, "key")) =>
+                {
+                    state = State::Assignment;
+                    assignments = Some(scopes.len());
+                }
+                (Token::Comma, _) if assignments == Some(scopes.len()) => state = State::Assignment,
+                // The next value in a tuple such as `(a, b) = (1, 'x')` or a
+                // list of insert values.
+                (Token::Comma, _)
+                    if matches!(
+                        scopes.last(),
+                        Some(State::ComparisonOperator | State::InsertValues)
+                    ) =>
+                {
+                    state = scopes.last().expect("scope is open").clone()
+                }
+                // The column, or columns in parentheses, that are assigned to
+                (
+                    Token::Keyword(Keyword::Other(_))
+                    | Token::Backticked(_)
+                    | Token::DoubleQuoted(_)
+                    | Token::Dot
+                    | Token::Comma
+                    | Token::ParentheseOpen,
+                    State::Assignment,
+                ) => (),
+                // The size of a window frame, as in `ROWS BETWEEN 5 PRECEDING
+                // AND CURRENT ROW`
+                (Token::Numeric(_), _) if self.is_frame_size(pos) => self.placeholder(pos),
                 (Token::Operator(Operator::Logical(LogicalOperator::In)), _) => {
                     state = State::Keyword
                 }
@@ -50,20 +212,56 @@ impl SqlSanitizer {
                     state = State::ComparisonOperator
                 }
                 (Token::Keyword(Keyword::Insert | Keyword::Into), _) => (),
-                (Token::Keyword(Keyword::Limit | Keyword::From), _) => state = State::Default,
+                (Token::Keyword(Keyword::Limit), _) => state = State::StructuralNumber,
+                (Token::Keyword(Keyword::From), _) => state = State::Default,
                 (Token::Keyword(Keyword::Where), _) => state = State::ComparisonOperator,
+                // A subquery instead of the arguments of a function
+                (Token::Keyword(Keyword::Select), State::KeywordScopeStarted) => {
+                    state = State::Default
+                }
                 (Token::Keyword(_), State::KeywordScopeStarted) => {
                     state = State::KeywordScopeStarted
                 }
+                // The value a simple CASE compares with, as in `CASE status
+                // WHEN 1 THEN`, like the values after THEN and ELSE
+                (Token::Keyword(Keyword::Other(_)), _) if self.sql.is_keyword(token, "when") => {
+                    state = State::ComparisonOperator
+                }
+                (Token::Keyword(Keyword::Other(_)), _) if self.is_structural_keyword(pos) => {
+                    state = State::StructuralNumber
+                }
                 (Token::Keyword(Keyword::Other(_)), _) => state = State::Keyword,
                 (Token::LiteralValueTypeIndicator(_), _) => (),
                 (Token::ParentheseOpen, State::ComparisonOperator) => {
                     state = State::ComparisonOperator
                 }
+                // The value in `CAST('x' AS text)`, without replacing the type
+                (Token::ParentheseOpen, State::Keyword)
+                    if self
+                        .previous_significant(pos)
+                        .is_some_and(|previous| self.is_one_of(previous, CASTS)) =>
+                {
+                    state = State::ComparisonOperator
+                }
+                // The size of a type such as `varchar(10)` in `::varchar(10)`
+                // or `CAST(a AS numeric(10, 2))`
+                (Token::ParentheseOpen, State::Keyword)
+                    if is_type_modifier(&self.sql, &tree, pos) =>
+                {
+                    state = State::Default
+                }
                 (Token::ParentheseOpen, State::Keyword) => state = State::KeywordScopeStarted,
                 (Token::ParentheseOpen, State::InsertValues) => (),
+                // Row counts and sample sizes, as in `TOP (10)` and
+                // `TABLESAMPLE SYSTEM (10 PERCENT)`
+                (Token::ParentheseOpen, State::StructuralNumber) => (),
+                (Token::Numeric(_), State::StructuralNumber) => {
+                    if self.options.redact_structural_numbers || !self.is_kept_row_count(pos) {
+                        self.placeholder(pos)
+                    }
+                }
                 (Token::SquareBracketOpen, State::Array) => state = State::ArrayStarted,
-                (Token::ParentheseClose, State::InsertValues) => {
+                (Token::ParentheseClose, State::InsertValues) if scopes.len() <= 1 => {
                     state = State::InsertValuesJustClosed
                 }
                 (Token::Comma, State::InsertValuesJustClosed) => (),
@@ -71,8 +269,11 @@ impl SqlSanitizer {
                     // We're past the first insert values clause. Remove every parentheses
                     // group and replace them with an ellipsis.
                     let start_pos = pos;
+                    let depth = tree.depth(start_pos);
                     let mut in_parentheses = true;
                     let mut kept = 0;
+                    let mut count = 0;
+                    let mut values = PlaceholderCounts::default();
 
                     loop {
                         if pos + kept >= self.sql.tokens.len() {
@@ -80,11 +281,25 @@ impl SqlSanitizer {
                         }
 
                         let keep = match self.sql.tokens[pos + kept] {
+                            Token::Semicolon => break,
                             Token::ParentheseClose => {
                                 in_parentheses = false;
                                 false
                             }
                             Token::ParentheseOpen => {
+                                let open = pos + kept;
+                                if tree.depth(open) == depth {
+                                    count += 1;
+                                    let close = tree
+                                        .closing(open)
+                                        .unwrap_or_else(|| self.end_of_statement(open));
+                                    values.add(self.value_counts(
+                                        &tree,
+                                        Some(open),
+                                        open + 1,
+                                        close,
+                                    ));
+                                }
                                 in_parentheses = true;
                                 false
                             }
@@ -107,12 +322,24 @@ impl SqlSanitizer {
                             pos += removed;
                         }
                     }
-                    self.ellipsis(start_pos);
+                    self.ellipsis(start_pos, count, values);
+                }
+                (Token::ParentheseClose | Token::SquareBracketClose, _) => {
+                    // Continue in the state of the group we're still in
+                    state = match scopes.len() {
+                        0 | 1 => State::Default,
+                        len => scopes[len - 2].clone(),
+                    }
                 }
-                (Token::ParentheseClose | Token::SquareBracketClose, _) => state = State::Default,
                 (Token::Dot, State::JoinOn) => (),
+                // A new statement starts after a semicolon
+                (Token::Semicolon, _) => {
+                    state = State::Default;
+                    scopes.clear();
+                }
                 (
                     Token::SingleQuoted(_)
+                    | Token::DollarQuoted(_)
                     | Token::Numeric(_)
                     | Token::Null
                     | Token::True
@@ -132,18 +359,17 @@ impl SqlSanitizer {
                     | State::Offset
                     | State::Between,
                 ) => {
-                    if !(self.sql.tokens.get(pos - 1) == Some(&Token::Dot)
-                        || self.sql.tokens.get(pos + 1) == Some(&Token::Dot))
-                    {
+                    if !self.in_dotted_path(pos) {
                         self.placeholder(pos)
                     }
                 }
                 // We're in an IN () or ARRAY[] or within the arguments of a function,
-                // and it starts with content. Remove everything until
-                // the closing parenthese and put one placeholder in between.
+                // and it starts with content. Remove everything until the closing
+                // parenthese or bracket of this group and put one placeholder in between.
                 (
                     Token::SingleQuoted(_)
                     | Token::DoubleQuoted(_)
+                    | Token::DollarQuoted(_)
                     | Token::Numeric(_)
                     | Token::Null
                     | Token::True
@@ -152,60 +378,691 @@ impl SqlSanitizer {
                     State::ArrayStarted | State::KeywordScopeStarted,
                 ) => {
                     let start_pos = pos;
-                    loop {
-                        if pos >= self.sql.tokens.len() {
-                            break;
-                        }
-                        match self.sql.tokens[pos] {
-                            Token::ParentheseClose => break,
-                            Token::SquareBracketClose => break,
-                            _ => {
-                                self.remove(pos);
-                                pos += 1;
-                            }
-                        }
+                    let open = tree.enclosing(pos);
+                    // A group that isn't closed ends with the statement
+                    let end_pos = open
+                        .and_then(|open| tree.closing(open))
+                        .unwrap_or(self.sql.tokens.len())
+                        .min(self.end_of_statement(pos));
+                    let placeholder = self.replacement(start_pos);
+                    let values = self.value_counts(&tree, open, start_pos, end_pos);
+                    while pos < end_pos {
+                        self.remove(pos);
+                        pos += 1;
                     }
-                    self.placeholder(start_pos);
+                    self.sql.tokens[start_pos] = if self.options.bucket_lists {
+                        Token::PlaceholderBucket(
+                            placeholder,
+                            ListBucket::for_count(values.total()),
+                            values,
+                        )
+                    } else {
+                        Token::PlaceholderList(placeholder, values)
+                    };
+                    // Continue at the closing token of the group
+                    pos = end_pos;
+                    continue;
                 }
                 // Remove comments
                 (Token::Comment(_), _) => {
                     self.remove(pos);
-                    if self.sql.tokens.get(pos - 1) == Some(&Token::Space) {
+                    if pos > 0 && self.sql.tokens[pos - 1] == Token::Space {
                         self.remove(pos - 1);
                     }
                 }
                 // Spaces and non-tokens don't influence the state
-                (Token::Space | Token::None, _) => (),
+                (Token::Space | Token::Newline | Token::None, _) => (),
                 // Keep state the same if we're in a insert values or keyword scope state
                 (_, State::InsertValues | State::KeywordScopeStarted) => (),
                 // Reset state to default if there were no matches
                 _ => state = State::Default,
             }
 
+            // Groups of insert values that were replaced by an ellipsis
+            // don't open a scope.
+            if opens_scope
+                && matches!(
+                    self.sql.tokens[token_pos],
+                    Token::ParentheseOpen | Token::SquareBracketOpen
+                )
+            {
+                scopes.push(state.clone());
+            } else if closes_scope {
+                scopes.pop();
+            }
+
+            pos += 1;
+        }
+
+        self.redact_credentials();
+        self.redact_bulk_loads();
+
+        if self.options.paranoid {
+            self.redact_all();
+        }
+
+        if self.options.detect_truncation && self.is_truncated() {
+            self.close_truncated();
+        }
+
+        self.sql
+    }
+
+    // The lexer splits hexadecimal numbers such as 0x1F and 0xFF, and
+    // numbers with an exponent such as 1.5e10 and 1.5E+10, into a number or
+    // `0x` and the rest. Join them into one number, so they're replaced as a
+    // whole.
+    fn join_numbers(&mut self) {
+        for pos in 0..self.sql.tokens.len() {
+            let tail = match self.sql.tokens.get(pos + 1) {
+                Some(Token::Keyword(Keyword::Other(tail))) => tail.clone(),
+                _ => continue,
+            };
+            let (start, hex) = match &self.sql.tokens[pos] {
+                Token::Numeric(slice) if slice.end == tail.start => {
+                    let number = self.sql.buffer_content(slice);
+                    (
+                        slice.start,
+                        number.starts_with("0x") || number.starts_with("0X"),
+                    )
+                }
+                Token::LiteralValueTypeIndicator(LiteralValueTypeIndicator::ZeroX)
+                    if tail.start >= 2 =>
+                {
+                    (tail.start - 2, true)
+                }
+                _ => continue,
+            };
+
+            let rest = self.sql.buffer_content(&tail);
+            let mut end = pos + 1;
+            let mut end_offset = tail.end;
+            if hex {
+                if !rest.chars().all(|c| c.is_ascii_hexdigit()) {
+                    continue;
+                }
+            } else if rest.eq_ignore_ascii_case("e") {
+                // 1.5E+10 is lexed as 1.5, E, + and 10
+                match (self.sql.tokens.get(pos + 2), self.sql.tokens.get(pos + 3)) {
+                    (
+                        Some(Token::Operator(Operator::Arithmetic(
+                            ArithmeticOperator::Plus | ArithmeticOperator::Minus,
+                        ))),
+                        Some(Token::Numeric(exponent)),
+                    ) if exponent.start == tail.end + 1 => {
+                        end = pos + 3;
+                        end_offset = exponent.end;
+                    }
+                    _ => continue,
+                }
+            } else {
+                let is_exponent = rest.strip_prefix(['e', 'E']).is_some_and(|exponent| {
+                    let exponent = exponent.trim_start_matches(['+', '-']);
+                    !exponent.is_empty() && exponent.chars().all(|c| c.is_ascii_digit())
+                });
+                if !is_exponent {
+                    continue;
+                }
+            }
+
+            self.sql.tokens[pos] = Token::Numeric(BufferSlice::new(start, end_offset));
+            for removed in pos + 1..=end {
+                self.remove(removed);
+            }
+        }
+    }
+
+    // Replace the values that follow keywords such as PASSWORD, IDENTIFIED
+    // BY and SECRET, and quoted user names and hosts such as 'user'@'host'.
+    // This doesn't depend on the state the rest of the query left us in.
+    fn redact_credentials(&mut self) {
+        let mut pos = 0;
+        while pos < self.sql.tokens.len() {
+            match &self.sql.tokens[pos] {
+                Token::SingleQuoted(_) | Token::DoubleQuoted(_)
+                    if self.sql.tokens.get(pos + 1) == Some(&Token::Unknown('@'))
+                        || (pos > 0 && self.sql.tokens[pos - 1] == Token::Unknown('@')) =>
+                {
+                    self.placeholder(pos)
+                }
+                token if self.is_credential_keyword(token) => {
+                    // Skip over the keywords, operators and parentheses in
+                    // between, as in IDENTIFIED WITH plugin BY 'value' or
+                    // PASSWORD = PASSWORD('value').
+                    let mut next = pos + 1;
+                    while let Some(token) = self.sql.tokens.get(next) {
+                        match token {
+                            Token::Space
+                            | Token::Newline
+                            | Token::None
+                            | Token::Colon
+                            | Token::ParentheseOpen
+                            | Token::Operator(Operator::Comparison(_))
+                            | Token::Keyword(Keyword::Other(_)) => next += 1,
+                            Token::SingleQuoted(_)
+                            | Token::DoubleQuoted(_)
+                            | Token::DollarQuoted(_)
+                            | Token::Numeric(_) => {
+                                self.placeholder(next);
+                                break;
+                            }
+                            _ => break,
+                        }
+                    }
+                }
+                _ => (),
+            }
             pos += 1;
         }
+    }
+
+    // Replaces the dollar quoted string at position `position` with its
+    // sanitized contents.
+    fn sanitize_body(&mut self, position: usize, slice: &BufferSlice) {
+        let (tag, body) = split_dollar_quoted(self.sql.buffer_content(slice));
+        let tag_len = tag.len();
+        // A body that was cut off is marked in the query around it
+        let options = SanitizeOptions {
+            detect_truncation: false,
+            ..self.options.clone()
+        };
+        let body = SqlSanitizer {
+            sql: lex(body.to_string()),
+            options,
+            body_depth: self.body_depth + 1,
+        }
+        .sanitize();
+        self.sql.tokens[position] = Token::DollarQuotedBody(
+            BufferSlice::new(slice.start, slice.start + tag_len),
+            Box::new(body),
+        );
+    }
+
+    // Replace the file paths, commands and connections in COPY, LOAD DATA
+    // and SELECT ... INTO OUTFILE statements, and remove the rows of data
+    // that follow COPY ... FROM STDIN.
+    fn redact_bulk_loads(&mut self) {
+        // The depth of the COPY statement we're in, so a FROM in a subquery
+        // such as `COPY (SELECT * FROM t) TO '...'` is left alone
+        let mut copy_depth: Option<usize> = None;
+        let mut depth = 0;
+        let mut from_stdin = false;
+        for pos in 0..self.sql.tokens.len() {
+            let token = &self.sql.tokens[pos];
+            let top_level = copy_depth == Some(depth);
+            match token {
+                Token::ParentheseOpen => depth += 1,
+                Token::ParentheseClose => depth = depth.saturating_sub(1),
+                // The rows of data start on the next line, also when the
+                // statement is not ended with a semicolon
+                Token::Semicolon | Token::Newline if from_stdin => {
+                    let start = if *token == Token::Semicolon {
+                        pos + 1
+                    } else {
+                        pos
+                    };
+                    self.remove_inline_data(start);
+                    copy_depth = None;
+                    from_stdin = false;
+                }
+                Token::Semicolon => copy_depth = None,
+                // COPY and UNLOAD, or the \copy command of psql
+                token
+                    if self.is_one_of(token, &["copy", "unload"])
+                        && (self.starts_statement(pos)
+                            || self.previous_significant(pos) == Some(&Token::Unknown('\\'))) =>
+                {
+                    copy_depth = Some(depth)
+                }
+                token if top_level && self.sql.is_keyword(token, "stdin") => from_stdin = true,
+                Token::Keyword(Keyword::From) if top_level => self.redact_location(pos + 1),
+                token
+                    if self.is_one_of(token, FILE_LOCATIONS)
+                        || (top_level && self.is_one_of(token, COPY_LOCATIONS)) =>
+                {
+                    self.redact_location(pos + 1)
+                }
+                _ => (),
+            }
+        }
+    }
+
+    // Replaces the quoted value at or after position `position`, skipping
+    // over whitespace, an equal sign and PROGRAM.
+    fn redact_location(&mut self, position: usize) {
+        let mut pos = position;
+        while let Some(token) = self.sql.tokens.get(pos) {
+            match token {
+                Token::Space
+                | Token::Newline
+                | Token::None
+                | Token::Operator(Operator::Comparison(ComparisonOperator::Equal)) => pos += 1,
+                token if self.sql.is_keyword(token, "program") => pos += 1,
+                Token::SingleQuoted(_) | Token::DoubleQuoted(_) | Token::DollarQuoted(_) => {
+                    self.placeholder(pos);
+                    break;
+                }
+                _ => break,
+            }
+        }
+    }
+
+    // Removes the rows of data that start at position `position`, up to and
+    // including the `\.` line that ends them or the end of the query.
+    fn remove_inline_data(&mut self, position: usize) {
+        let mut end = self.sql.tokens.len();
+        for pos in position..self.sql.tokens.len() {
+            if self.sql.tokens[pos] == Token::Unknown('\\')
+                && self.sql.tokens.get(pos + 1) == Some(&Token::Dot)
+                && self.sql.tokens[pos - 1] == Token::Newline
+            {
+                end = pos + 2;
+                break;
+            }
+        }
+        for pos in position..end {
+            self.remove(pos);
+        }
+    }
+
+    fn is_credential_keyword(&self, token: &Token) -> bool {
+        self.is_one_of(token, CREDENTIALS)
+    }
+
+    // Replace every value that is still in the query, except for numbers
+    // after one of the allowed keywords.
+    fn redact_all(&mut self) {
+        let mut previous: Option<usize> = None;
+        for pos in 0..self.sql.tokens.len() {
+            match &self.sql.tokens[pos] {
+                Token::SingleQuoted(_) | Token::DollarQuoted(_) => self.placeholder(pos),
+                Token::Numeric(_) => {
+                    let allowed = previous.is_some_and(|previous| self.is_allowlisted(previous));
+                    if !allowed {
+                        self.placeholder(pos);
+                    }
+                }
+                Token::Space | Token::Newline | Token::None | Token::Comment(_) => continue,
+                _ => (),
+            }
+            previous = Some(pos);
+        }
+    }
+
+    // Whether numbers after the token at position `position` are kept in
+    // paranoid mode.
+    fn is_allowlisted(&self, position: usize) -> bool {
+        let token = &self.sql.tokens[position];
+        self.options
+            .numeric_allowlist
+            .iter()
+            .any(|keyword| match token {
+                Token::Keyword(Keyword::Limit) => keyword.eq_ignore_ascii_case("limit"),
+                Token::Keyword(Keyword::Offset) => keyword.eq_ignore_ascii_case("offset"),
+                token => self.sql.is_keyword(token, keyword),
+            })
+    }
+
+    // A query that ends in an unterminated quote or comment, in an open
+    // list or halfway an expression was most likely cut off.
+    fn is_truncated(&self) -> bool {
+        let cut_off_in_token = self.sql.diagnostics.iter().any(|diagnostic| {
+            matches!(
+                diagnostic.kind,
+                DiagnosticKind::UnterminatedQuote(_)
+                    | DiagnosticKind::UnterminatedComment
+                    | DiagnosticKind::Unbalanced('(' | '[')
+            )
+        });
+        cut_off_in_token
+            || matches!(
+                self.last_significant(),
+                Some((_, Token::Comma | Token::Operator(_)))
+            )
+    }
+
+    // Make sure a query that was cut off doesn't end in a partial literal
+    // or with unbalanced parentheses, and mark it as truncated.
+    fn close_truncated(&mut self) {
+        match self.last_significant() {
+            Some((
+                pos,
+                Token::SingleQuoted(_) | Token::DoubleQuoted(_) | Token::DollarQuoted(_),
+            )) => {
+                let unterminated = self.sql.diagnostics.iter().any(|diagnostic| {
+                    matches!(
+                        diagnostic.kind,
+                        DiagnosticKind::UnterminatedQuote('\'' | '"' | '$')
+                    )
+                });
+                if unterminated {
+                    self.placeholder(pos)
+                }
+            }
+            Some((pos, Token::Comma)) => self.remove(pos),
+            Some((_, Token::Operator(_))) => {
+                self.sql.tokens.push(Token::Space);
+                self.sql
+                    .tokens
+                    .push(Token::Placeholder(Placeholder::Redacted(
+                        ValueKind::Unknown,
+                    )));
+            }
+            _ => (),
+        }
+
+        // Remove trailing whitespace so the closing tokens follow the
+        // content directly.
+        while let Some(Token::Space | Token::Newline | Token::None) = self.sql.tokens.last() {
+            self.sql.tokens.pop();
+        }
+
+        let mut open = Vec::new();
+        for token in self.sql.tokens.iter() {
+            match token {
+                Token::ParentheseOpen => open.push(Token::ParentheseClose),
+                Token::SquareBracketOpen => open.push(Token::SquareBracketClose),
+                Token::ParentheseClose | Token::SquareBracketClose
+                    if open.last() == Some(token) =>
+                {
+                    open.pop();
+                }
+                _ => (),
+            }
+        }
+        while let Some(close) = open.pop() {
+            self.sql.tokens.push(close);
+        }
+
+        if !self.sql.tokens.is_empty() {
+            self.sql.tokens.push(Token::Space);
+        }
+        self.sql.tokens.push(Token::Truncated);
+    }
 
+    // Returns the last token that is not whitespace or removed, with its position.
+    fn last_significant(&self) -> Option<(usize, &Token)> {
         self.sql
+            .tokens
+            .iter()
+            .enumerate()
+            .rev()
+            .find(|(_, token)| !matches!(token, Token::Space | Token::Newline | Token::None))
+    }
+
+    // Whether the token at position `position` is the first one of a
+    // statement.
+    fn starts_statement(&self, position: usize) -> bool {
+        match self.previous_significant(position) {
+            None | Some(Token::Semicolon) => true,
+            Some(token) => self.sql.is_keyword(token, "begin"),
+        }
+    }
+
+    // Returns the last token before position `position` that is not
+    // whitespace or removed.
+    fn previous_significant(&self, position: usize) -> Option<&Token> {
+        self.previous_significant_position(position)
+            .map(|previous| &self.sql.tokens[previous])
+    }
+
+    // Returns the position of the last token before position `position`
+    // that is not whitespace or removed.
+    fn previous_significant_position(&self, position: usize) -> Option<usize> {
+        self.sql.tokens[..position]
+            .iter()
+            .rposition(|token| !matches!(token, Token::Space | Token::Newline | Token::None))
+    }
+
+    // Whether `token` is one of the given keywords
+    fn is_one_of(&self, token: &Token, keywords: &[&str]) -> bool {
+        keywords
+            .iter()
+            .any(|keyword| self.sql.is_keyword(token, keyword))
+    }
+
+    // Whether the value at position `position` has a type in front of it,
+    // as in `DATE '2024-01-01'` and `INTERVAL '3 days'`, or is cast with
+    // `::`, as in `'abc'::bytea`.
+    fn is_typed_literal(&self, position: usize) -> bool {
+        let prefixed = match self.previous_significant(position) {
+            Some(Token::LiteralValueTypeIndicator(_)) => true,
+            Some(previous) => self.is_one_of(previous, TYPE_PREFIXES),
+            None => false,
+        };
+        let mut next = self.sql.tokens[position + 1..]
+            .iter()
+            .filter(|token| !matches!(token, Token::Space | Token::Newline | Token::None));
+        prefixed || (next.next() == Some(&Token::Colon) && next.next() == Some(&Token::Colon))
+    }
+
+    // Whether the keyword at position `position` is followed by a row
+    // count or sample size: `FETCH FIRST 10`, `FETCH NEXT 10`, `TOP 10`
+    // and `TABLESAMPLE BERNOULLI (5)`.
+    fn is_structural_keyword(&self, position: usize) -> bool {
+        let token = &self.sql.tokens[position];
+        ["top", "first", "next", "tablesample"]
+            .iter()
+            .any(|keyword| self.sql.is_keyword(token, keyword))
+            || self
+                .previous_significant(position)
+                .is_some_and(|previous| self.sql.is_keyword(previous, "tablesample"))
+    }
+
+    // Whether the row count at position `position` is kept unless structural
+    // numbers are redacted, as in `LIMIT 10`, `FETCH FIRST 10 ROWS` and
+    // `TOP 10`. Numbers in parentheses such as `TOP (10)` and `TABLESAMPLE
+    // SYSTEM (10)` are replaced, except for `LIMIT (10)`.
+    fn is_kept_row_count(&self, position: usize) -> bool {
+        match self.previous_significant_position(position) {
+            Some(open) if self.sql.tokens[open] == Token::ParentheseOpen => {
+                self.previous_significant(open) == Some(&Token::Keyword(Keyword::Limit))
+            }
+            _ => true,
+        }
+    }
+
+    // Whether the number at position `position` is followed by PRECEDING
+    // or FOLLOWING in a window frame.
+    fn is_frame_size(&self, position: usize) -> bool {
+        self.sql.tokens[position + 1..]
+            .iter()
+            .find(|token| !matches!(token, Token::Space | Token::Newline | Token::None))
+            .is_some_and(|next| {
+                self.sql.is_keyword(next, "preceding") || self.sql.is_keyword(next, "following")
+            })
+    }
+
+    // Position of the semicolon that ends the statement the token at
+    // position `position` is in, or the end of the query.
+    fn end_of_statement(&self, position: usize) -> usize {
+        self.sql.tokens[position..]
+            .iter()
+            .position(|token| *token == Token::Semicolon)
+            .map_or(self.sql.tokens.len(), |end| position + end)
+    }
+
+    // Whether `token` ends a list of assignments
+    fn ends_assignments(&self, token: &Token) -> bool {
+        match token {
+            Token::Keyword(Keyword::Where | Keyword::From | Keyword::Select | Keyword::Limit)
+            | Token::Semicolon => true,
+            token => ["returning", "when", "output", "order"]
+                .iter()
+                .any(|keyword| self.sql.is_keyword(token, keyword)),
+        }
+    }
+
+    // Whether the token at position `position` is a component of a dotted
+    // path such as `"schema"."table"`, which makes it an identifier.
+    fn in_dotted_path(&self, position: usize) -> bool {
+        (position > 0 && self.sql.tokens[position - 1] == Token::Dot)
+            || self.sql.tokens.get(position + 1) == Some(&Token::Dot)
     }
 
     fn remove(&mut self, position: usize) {
         self.sql.tokens[position] = Token::None;
     }
 
-    // Replaces the token at position `position` with a placeholder.
+    // Replaces the token at position `position` with a placeholder. A word
+    // that is glued to a number, as in `1abc`, is part of the value.
     fn placeholder(&mut self, position: usize) {
-        self.sql.tokens[position] = Token::Placeholder;
+        let number = matches!(self.sql.tokens[position], Token::Numeric(_));
+        self.sql.tokens[position] = Token::Placeholder(self.replacement(position));
+        if number
+            && matches!(
+                self.sql.tokens.get(position + 1),
+                Some(Token::Keyword(Keyword::Other(_)))
+            )
+        {
+            self.remove(position + 1);
+        }
+    }
+
+    // The placeholder for the value at position `position`
+    fn replacement(&self, position: usize) -> Placeholder {
+        let kind = match &self.sql.tokens[position] {
+            Token::NumberedPlaceholder(_) | Token::Placeholder(Placeholder::Bound) => {
+                return Placeholder::Bound
+            }
+            Token::Numeric(slice) => {
+                let value = self.sql.buffer_content(slice);
+                if value.starts_with("0x") || value.starts_with("0X") {
+                    ValueKind::Hex
+                } else {
+                    ValueKind::Number
+                }
+            }
+            Token::Null => ValueKind::Null,
+            Token::True | Token::False => ValueKind::Boolean,
+            // Quoted values can have a type indicator in front of them
+            _ => match self.previous_significant(position) {
+                Some(Token::LiteralValueTypeIndicator(
+                    LiteralValueTypeIndicator::Date
+                    | LiteralValueTypeIndicator::Time
+                    | LiteralValueTypeIndicator::Timestamp,
+                )) => ValueKind::Date,
+                Some(previous) if self.sql.is_keyword(previous, "zone") => ValueKind::Date,
+                Some(Token::LiteralValueTypeIndicator(
+                    LiteralValueTypeIndicator::X | LiteralValueTypeIndicator::ZeroX,
+                )) => ValueKind::Hex,
+                _ => ValueKind::String,
+            },
+        };
+        Placeholder::Redacted(kind)
+    }
+
+    // Replaces the token at position `position` with an ellipsis for
+    // `count` value lists with `values` in them.
+    fn ellipsis(&mut self, position: usize, count: usize, values: PlaceholderCounts) {
+        self.sql.tokens[position] = Token::Ellipsis(count, values);
+    }
+
+    // Counts the values from position `start` up to position `end` in the
+    // group that is opened at position `open`. A value is bound if it
+    // starts with a bound parameter, every other value is replaced.
+    fn value_counts(
+        &self,
+        tree: &TokenTree,
+        open: Option<usize>,
+        start: usize,
+        end: usize,
+    ) -> PlaceholderCounts {
+        let mut counts = PlaceholderCounts::default();
+        let mut starts_value = true;
+        for pos in start..end {
+            match &self.sql.tokens[pos] {
+                Token::Comma if tree.enclosing(pos) == open => starts_value = true,
+                Token::Space | Token::Newline | Token::None | Token::Comment(_) => (),
+                _ if !starts_value => (),
+                Token::NumberedPlaceholder(_) | Token::Placeholder(Placeholder::Bound) => {
+                    counts.bound += 1;
+                    starts_value = false;
+                }
+                _ => {
+                    counts.redacted += 1;
+                    starts_value = false;
+                }
+            }
+        }
+        counts
     }
+}
+
+// Whether the dollar quoted string at position `position` is the body of a
+// function or DO block, as in `AS $$ ... $$` and `DO $$ ... $$`, or a
+// query that is executed from such a body.
+pub(crate) fn is_body(sql: &Sql, position: usize) -> bool {
+    sql.tokens[..position]
+        .iter()
+        .rev()
+        .find(|token| is_significant(token))
+        .is_some_and(|previous| {
+            ["as", "do", "execute"]
+                .iter()
+                .any(|keyword| sql.is_keyword(previous, keyword))
+        })
+}
 
-    // Replaces the token at position `position` with an ellipsis.
-    fn ellipsis(&mut self, position: usize) {
-        self.sql.tokens[position] = Token::Ellipsis;
+// Whether the parenthese at position `position` follows the name of a type,
+// after `::` or AS in a cast, as in `::varchar(10)` and `CAST(a AS
+// numeric(10, 2))`.
+pub(crate) fn is_type_modifier(sql: &Sql, tree: &TokenTree, position: usize) -> bool {
+    let previous = |position: usize| sql.tokens[..position].iter().rposition(is_significant);
+    let name = match previous(position) {
+        Some(name) => name,
+        None => return false,
+    };
+    match previous(name).map(|previous| &sql.tokens[previous]) {
+        Some(Token::Colon) => true,
+        Some(token) if sql.is_keyword(token, "as") => tree
+            .enclosing(name)
+            .and_then(previous)
+            .is_some_and(|function| {
+                CASTS
+                    .iter()
+                    .any(|cast| sql.is_keyword(&sql.tokens[function], cast))
+            }),
+        _ => false,
     }
 }
 
+// Splits a dollar quoted string such as `$body$ ... $body$` into its
+// opening tag and its contents. The closing tag can be missing.
+pub(crate) fn split_dollar_quoted(content: &str) -> (&str, &str) {
+    let tag_len = content[1..].find('$').map_or(content.len(), |end| end + 2);
+    let (tag, rest) = content.split_at(tag_len);
+    (tag, rest.strip_suffix(tag).unwrap_or(rest))
+}
+
 #[cfg(test)]
 mod tests {
-    use super::super::sanitize_string;
+    use super::super::{
+        lex, sanitize, sanitize_with_options, verify_sanitized, write, Placeholder,
+        PlaceholderCounts, SanitizeOptions, Token, ValueKind,
+    };
+
+    // Sanitizes with the default options and checks that `verify_sanitized`
+    // finds nothing in the output, so every query in these tests is also a
+    // test of the verifier.
+    fn sanitize_string(sql: String) -> String {
+        let sanitized = sanitize(lex(sql));
+        let written = write(sanitized.clone());
+        assert_eq!(verify_sanitized(&sanitized), Ok(()), "{}", written);
+        written
+    }
+
+    // Like `sanitize_string`, for queries with a value that the default
+    // options leave alone, such as a number in a select list. The verifier
+    // must report it and find nothing after paranoid sanitizing.
+    fn sanitize_leaving_values(sql: String) -> String {
+        let sanitized = sanitize(lex(sql.clone()));
+        let written = write(sanitized.clone());
+        assert!(verify_sanitized(&sanitized).is_err(), "{}", written);
+        let paranoid = sanitize_with_options(lex(sql), &helpers::paranoid());
+        assert_eq!(verify_sanitized(&paranoid), Ok(()), "{}", write(paranoid));
+        written
+    }
 
     #[test]
     fn test_empty() {
@@ -297,6 +1154,39 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_select_where_with_function_nested_args() {
+        assert_eq!(
+            sanitize_string(
+                "SELECT `table`.* FROM `table` WHERE `name` = COMMAND('a', LOWER('b'), 'c') LIMIT 1;"
+                    .to_string()
+            ),
+            "SELECT `table`.* FROM `table` WHERE `name` = COMMAND(?) LIMIT 1;"
+        );
+    }
+
+    #[test]
+    fn test_select_where_with_function_subquery_arg() {
+        assert_eq!(
+            sanitize_string(
+                "SELECT `table`.* FROM `table` WHERE `name` = COMMAND(`field`, (SELECT `a` FROM `b`), 'secret') LIMIT 1;"
+                    .to_string()
+            ),
+            "SELECT `table`.* FROM `table` WHERE `name` = COMMAND(`field`, (SELECT `a` FROM `b`), ?) LIMIT 1;"
+        );
+    }
+
+    #[test]
+    fn test_select_where_nested_scopes() {
+        assert_eq!(
+            sanitize_string(
+                "SELECT * FROM `table` WHERE (`a` = 1 AND `b` IN (1, 2) AND `c` = 'secret');"
+                    .to_string()
+            ),
+            "SELECT * FROM `table` WHERE (`a` = ? AND `b` IN (?) AND `c` = ?);"
+        );
+    }
+
     #[test]
     fn test_select_where_like() {
         assert_eq!(
@@ -412,6 +1302,17 @@ mod tests {
         )
     }
 
+    #[test]
+    fn test_select_where_dollar_quoted() {
+        assert_eq!(
+            sanitize_string(
+                "SELECT * FROM \"table\" WHERE \"name\" = $$secret$$ OR \"name\" IN ($tag$a$tag$, $$b$$);"
+                    .to_string()
+            ),
+            "SELECT * FROM \"table\" WHERE ? = ? OR ? IN (?);"
+        );
+    }
+
     #[test]
     fn test_select_in_values() {
         assert_eq!(
@@ -422,6 +1323,89 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_select_in_values_count() {
+        let sql = sanitize(lex(
+            "SELECT * FROM `table` WHERE `id` IN (1, 2, LOWER('a')) AND `b` IN (1)".to_string(),
+        ));
+
+        assert_eq!(
+            sql.tokens
+                .iter()
+                .filter(|token| matches!(token, Token::PlaceholderList(..)))
+                .collect::<Vec<_>>(),
+            vec![
+                &Token::PlaceholderList(
+                    Placeholder::Redacted(ValueKind::Number),
+                    PlaceholderCounts {
+                        bound: 0,
+                        redacted: 3,
+                    }
+                ),
+                &Token::PlaceholderList(
+                    Placeholder::Redacted(ValueKind::Number),
+                    PlaceholderCounts {
+                        bound: 0,
+                        redacted: 1,
+                    }
+                )
+            ]
+        );
+    }
+
+    #[test]
+    fn test_select_in_values_bucketed() {
+        let list = |count: usize| {
+            (1..=count)
+                .map(|i| i.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+
+        assert_eq!(
+            helpers::sanitize_bucketed(&format!(
+                "SELECT * FROM `table` WHERE `a` IN ({}) AND `b` IN ({}) AND `c` IN ({}) AND `d` IN ({})",
+                list(1),
+                list(10),
+                list(11),
+                list(101)
+            )),
+            "SELECT * FROM `table` WHERE `a` IN (?) AND `b` IN (?, ?+) AND `c` IN (?, ?, ?+) AND `d` IN (?, ?, ?, ?+)"
+        );
+        assert_eq!(
+            helpers::sanitize_bucketed("SELECT * FROM \"table\" WHERE \"a\" = ARRAY['a', 'b']"),
+            "SELECT * FROM \"table\" WHERE ? = ARRAY[?, ?+]"
+        );
+    }
+
+    #[test]
+    fn test_placeholder_kinds() {
+        let sql = sanitize(lex(
+            "SELECT * FROM t WHERE a = 'x' AND b = 1 AND c = NULL AND d = TRUE AND e > DATE '2024-01-01' AND f = X'1F' AND g = 0x1F AND h = ?"
+                .to_string(),
+        ));
+
+        assert_eq!(
+            sql.tokens
+                .iter()
+                .filter_map(|token| match token {
+                    Token::Placeholder(placeholder) => Some(*placeholder),
+                    _ => None,
+                })
+                .collect::<Vec<_>>(),
+            vec![
+                Placeholder::Redacted(ValueKind::String),
+                Placeholder::Redacted(ValueKind::Number),
+                Placeholder::Redacted(ValueKind::Null),
+                Placeholder::Redacted(ValueKind::Boolean),
+                Placeholder::Redacted(ValueKind::Date),
+                Placeholder::Redacted(ValueKind::Hex),
+                Placeholder::Redacted(ValueKind::Hex),
+                Placeholder::Bound,
+            ]
+        );
+    }
+
     #[test]
     fn test_select_in_param_prefix() {
         assert_eq!(
@@ -565,6 +1549,21 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_insert_multiple_values_count() {
+        let sql = sanitize(lex(
+            "INSERT INTO `table` (`a`) VALUES (1), (LOWER('b')), (3), (4);".to_string(),
+        ));
+
+        assert!(sql.tokens.contains(&Token::Ellipsis(
+            3,
+            PlaceholderCounts {
+                bound: 0,
+                redacted: 3,
+            }
+        )));
+    }
+
     #[test]
     fn test_insert_multiple_values_with_spaces() {
         assert_eq!(
@@ -581,6 +1580,16 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_insert_values_with_function() {
+        assert_eq!(
+            sanitize_string(
+                "INSERT INTO `table` (`field1`, `field2`) VALUES (NOW(), 'value');".to_string()
+            ),
+            "INSERT INTO `table` (`field1`, `field2`) VALUES (NOW(), ?);"
+        );
+    }
+
     #[test]
     fn test_insert_returning() {
         assert_eq!(
@@ -643,10 +1652,18 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_comment_at_start() {
+        assert_eq!(
+            sanitize_string("/* This is a comment */ SELECT * FROM table".to_string()),
+            " SELECT * FROM table"
+        );
+    }
+
     #[test]
     fn test_comment_end_of_subquery() {
         assert_eq!(
-            sanitize_string("SELECT COUNT(*) FROM (SELECT (*) from table WHERE table.attr = 123 /* traceparent=00-a7bd9142c227de0d3c1dccb3a21800b8-1e30b841ea8c9b77-01 */) AS 'sub'".to_string()),
+            sanitize_leaving_values("SELECT COUNT(*) FROM (SELECT (*) from table WHERE table.attr = 123 /* traceparent=00-a7bd9142c227de0d3c1dccb3a21800b8-1e30b841ea8c9b77-01 */) AS 'sub'".to_string()),
             "SELECT COUNT(*) FROM (SELECT (*) FROM table WHERE table.attr = ?) AS 'sub'"
         );
     }
@@ -665,7 +1682,7 @@ mod tests {
                 "SELECT table.*, NULLIF((table2.json_col #>> '{obj1,obj2}')::float, 0) FROM table"
                     .to_string()
             ),
-            "SELECT table.*, NULLIF((table2.json_col #>> ?)::float, 0) FROM table"
+            "SELECT table.*, NULLIF((table2.json_col #>> ?)::float, ?) FROM table"
         )
     }
 
@@ -734,4 +1751,758 @@ mod tests {
             r#"SELECT "table"."id" FROM "table" WHERE ("table"."data" = ?);"#
         );
     }
+
+    #[test]
+    fn test_truncated_single_quote() {
+        assert_eq!(
+            helpers::sanitize_truncated("SELECT * FROM `table` WHERE `name` = 'sec"),
+            "SELECT * FROM `table` WHERE `name` = ? ...TRUNCATED"
+        );
+    }
+
+    #[test]
+    fn test_function_body() {
+        let sql = "CREATE FUNCTION f() RETURNS int AS $$ BEGIN SELECT a FROM t WHERE b = 'secret'; v := 'x'; RETURN 1; END $$ LANGUAGE plpgsql";
+
+        assert_eq!(
+            sanitize_string(sql.to_string()),
+            "CREATE FUNCTION f() RETURNS int AS ? LANGUAGE plpgsql"
+        );
+        assert_eq!(
+            helpers::sanitize_bodies(sql),
+            "CREATE FUNCTION f() RETURNS int AS $$ BEGIN SELECT a FROM t WHERE b = ?; v := ?; RETURN 1; END $$ LANGUAGE plpgsql"
+        );
+    }
+
+    #[test]
+    fn test_do_block_replaced_by_default() {
+        assert_eq!(
+            sanitize_string("DO $$ BEGIN PERFORM 'secret'; END $$".to_string()),
+            "DO ?"
+        );
+        assert_eq!(
+            sanitize_leaving_values(
+                "DO $body$ BEGIN UPDATE t SET a = 'secret'; END $body$; SELECT 1".to_string()
+            ),
+            "DO ?; SELECT 1"
+        );
+    }
+
+    #[test]
+    fn test_do_block() {
+        assert_eq!(
+            helpers::sanitize_bodies(
+                "DO $body$\nBEGIN\n  UPDATE t SET a = 'x' WHERE id = 1;\n  EXECUTE $q$SELECT 1 FROM t WHERE c = 2$q$;\nEND\n$body$"
+            ),
+            "DO $body$\nBEGIN\n  UPDATE t SET a = ? WHERE id = ?;\n  EXECUTE $q$SELECT 1 FROM t WHERE c = ?$q$;\nEND\n$body$"
+        );
+        // Only bodies are sanitized, other dollar quoted strings are values
+        assert_eq!(
+            helpers::sanitize_bodies("SELECT $$a$$ FROM t WHERE b = $$c$$"),
+            "SELECT $$a$$ FROM t WHERE b = ?"
+        );
+    }
+
+    #[test]
+    fn test_unterminated_body() {
+        let options = SanitizeOptions {
+            sanitize_bodies: true,
+            detect_truncation: true,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            write(sanitize_with_options(
+                lex("DO $$ BEGIN PERFORM f('a'".to_string()),
+                &options
+            )),
+            "DO $$ BEGIN PERFORM f(?$$ ...TRUNCATED"
+        );
+    }
+
+    #[test]
+    fn test_deeply_nested_bodies() {
+        let nest = |depth: usize| {
+            (0..depth).fold("SELECT 'a'".to_string(), |sql, tag| {
+                format!("DO $b{}$ {} $b{}$", tag, sql, tag)
+            })
+        };
+
+        // Bodies nested deeper than eight levels are replaced as a whole
+        assert_eq!(
+            helpers::sanitize_bodies(&nest(10)),
+            "DO $b9$ DO $b8$ DO $b7$ DO $b6$ DO $b5$ DO $b4$ DO $b3$ DO $b2$ DO ? $b2$ $b3$ $b4$ $b5$ $b6$ $b7$ $b8$ $b9$"
+        );
+        assert!(helpers::sanitize_bodies(&nest(10_000)).starts_with("DO $b9999$ DO $b9998$"));
+    }
+
+    #[test]
+    fn test_deeply_nested_groups() {
+        let sql = format!("SELECT {}", "(".repeat(300_000));
+        assert_eq!(sanitize_string(sql.clone()), sql);
+
+        let sql = format!(
+            "SELECT * FROM t WHERE a IN {}(1",
+            "(SELECT a FROM t WHERE a IN ".repeat(50_000)
+        );
+        assert!(sanitize_string(sql).ends_with("(SELECT a FROM t WHERE a IN (?"));
+    }
+
+    #[test]
+    fn test_copy_postgres() {
+        assert_eq!(
+            sanitize_string(
+                "COPY t (a, b) FROM '/home/user/export.csv' WITH (FORMAT csv)".to_string()
+            ),
+            "COPY t (a, b) FROM ? WITH (FORMAT csv)"
+        );
+        assert_eq!(
+            sanitize_string(
+                "COPY (SELECT * FROM t WHERE a = 1) TO PROGRAM 'gzip > /tmp/t.gz'".to_string()
+            ),
+            "COPY (SELECT * FROM t WHERE a = ?) TO PROGRAM ?"
+        );
+        assert_eq!(
+            sanitize_string("COPY (SELECT * FROM \"users\" WHERE a = 1) TO '/tmp/x'".to_string()),
+            "COPY (SELECT * FROM \"users\" WHERE a = ?) TO ?"
+        );
+        assert_eq!(
+            sanitize_string("\\copy t from '/tmp/t.csv' csv".to_string()),
+            "\\copy t FROM ? csv"
+        );
+    }
+
+    #[test]
+    fn test_copy_from_stdin() {
+        assert_eq!(
+            sanitize_leaving_values(
+                "COPY t (a, b) FROM STDIN;\n1\tbob\n2\t'alice'\n\\.\nSELECT 1 FROM t WHERE a = 2;"
+                    .to_string()
+            ),
+            "COPY t (a, b) FROM STDIN;\nSELECT 1 FROM t WHERE a = ?;"
+        );
+        assert_eq!(
+            sanitize_string("COPY t FROM stdin;\n1\tbob".to_string()),
+            "COPY t FROM stdin;"
+        );
+        // Without a semicolon the data starts on the next line
+        assert_eq!(
+            sanitize_string("COPY t FROM STDIN\n1\tbob@example.com\n\\.".to_string()),
+            "COPY t FROM STDIN"
+        );
+        assert_eq!(
+            sanitize_leaving_values(
+                "COPY t FROM STDIN WITH (FORMAT csv)\n1,bob@example.com\n\\.\nSELECT 1".to_string()
+            ),
+            "COPY t FROM STDIN WITH (FORMAT csv)\nSELECT 1"
+        );
+    }
+
+    #[test]
+    fn test_copy_cloud_storage() {
+        assert_eq!(
+            sanitize_string("COPY INTO t FROM 's3://bucket/path' CONNECTION = 'conn'".to_string()),
+            "COPY INTO t FROM ? CONNECTION = ?"
+        );
+        assert_eq!(
+            sanitize_string(
+                "UNLOAD ('SELECT 1') TO 's3://bucket/path' IAM_ROLE 'arn:aws:iam::1:role/r'"
+                    .to_string()
+            ),
+            "UNLOAD (?) TO ? IAM_ROLE ?"
+        );
+    }
+
+    #[test]
+    fn test_load_data_mysql() {
+        assert_eq!(
+            sanitize_leaving_values(
+                "LOAD DATA LOCAL INFILE '/path/t.csv' INTO TABLE `t` FIELDS TERMINATED BY ',' IGNORE 1 LINES (`a`, `b`)"
+                    .to_string()
+            ),
+            "LOAD DATA LOCAL INFILE ? INTO TABLE `t` FIELDS TERMINATED BY ',' IGNORE 1 LINES (`a`, `b`)"
+        );
+    }
+
+    #[test]
+    fn test_select_into_outfile_mysql() {
+        assert_eq!(
+            sanitize_leaving_values(
+                "SELECT `a` INTO OUTFILE '/tmp/t.csv' FIELDS TERMINATED BY ',' FROM `t`"
+                    .to_string()
+            ),
+            "SELECT `a` INTO OUTFILE ? FIELDS TERMINATED BY ',' FROM `t`"
+        );
+        assert_eq!(
+            sanitize_string("SELECT `a` FROM `t` INTO DUMPFILE \"/tmp/t\"".to_string()),
+            "SELECT `a` FROM `t` INTO DUMPFILE ?"
+        );
+    }
+
+    #[test]
+    fn test_typed_literals() {
+        assert_eq!(
+            sanitize_string(
+                "SELECT DATE '2024-01-01', INTERVAL '3 days', TIMESTAMP WITH TIME ZONE '2024-01-01 00:00', x'1F' FROM t"
+                    .to_string()
+            ),
+            "SELECT DATE ?, INTERVAL ?, TIMESTAMP WITH TIME ZONE ?, x? FROM t"
+        );
+        assert_eq!(
+            sanitize_string(
+                "SELECT * FROM t WHERE a > DATE '2024-01-01' AND b < now() - INTERVAL '3 days'"
+                    .to_string()
+            ),
+            "SELECT * FROM t WHERE a > DATE ? AND b < now() - INTERVAL ?"
+        );
+    }
+
+    #[test]
+    fn test_casts() {
+        assert_eq!(
+            sanitize_string(
+                "SELECT '{1,2,3}'::int[], 'abc'::bytea, 1 :: bigint, CAST('x' AS text) FROM t"
+                    .to_string()
+            ),
+            "SELECT ?::int[], ?::bytea, ? :: bigint, CAST(? AS text) FROM t"
+        );
+        assert_eq!(
+            sanitize_string(
+                "SELECT CAST(a AS int) FROM t WHERE b = CAST(1 AS numeric(10, 2)) AND c = 'x'::varchar(10)"
+                    .to_string()
+            ),
+            "SELECT CAST(a AS int) FROM t WHERE b = CAST(? AS numeric(10, 2)) AND c = ?::varchar(10)"
+        );
+        assert_eq!(
+            sanitize_string("INSERT INTO t VALUES (DATE '2024-01-01', 'x'::text)".to_string()),
+            "INSERT INTO t VALUES (DATE ?, ?::text)"
+        );
+    }
+
+    #[test]
+    fn test_typed_literal_kind() {
+        let sql = sanitize(lex(
+            "SELECT TIMESTAMP WITH TIME ZONE '2024-01-01 00:00', INTERVAL '1 day'".to_string(),
+        ));
+
+        assert_eq!(
+            sql.tokens[10],
+            Token::Placeholder(Placeholder::Redacted(ValueKind::Date))
+        );
+        assert_eq!(
+            sql.tokens[15],
+            Token::Placeholder(Placeholder::Redacted(ValueKind::String))
+        );
+    }
+
+    #[test]
+    fn test_case_expressions() {
+        assert_eq!(
+            sanitize_string(
+                "SELECT CASE WHEN status = 1 THEN 'active' ELSE 'x' END FROM t".to_string()
+            ),
+            "SELECT CASE WHEN status = ? THEN ? ELSE ? END FROM t"
+        );
+        assert_eq!(
+            sanitize_string(
+                "SELECT CASE status WHEN 1 THEN 'a' WHEN 'b' THEN 2 END AS s FROM t WHERE a = 1"
+                    .to_string()
+            ),
+            "SELECT CASE status WHEN ? THEN ? WHEN ? THEN ? END AS s FROM t WHERE a = ?"
+        );
+    }
+
+    #[test]
+    fn test_structural_numbers() {
+        let sql = "SELECT TOP 10 SUM(a) OVER (ORDER BY b ROWS BETWEEN 5 PRECEDING AND 2 FOLLOWING) FROM t TABLESAMPLE BERNOULLI (5) REPEATABLE (42) WHERE c = 1 ORDER BY b OFFSET 5 ROWS FETCH FIRST 10 ROWS ONLY";
+
+        assert_eq!(
+            sanitize_string(sql.to_string()),
+            "SELECT TOP 10 SUM(a) OVER (ORDER BY b ROWS BETWEEN ? PRECEDING AND ? FOLLOWING) FROM t TABLESAMPLE BERNOULLI (?) REPEATABLE (?) WHERE c = ? ORDER BY b OFFSET ? ROWS FETCH FIRST 10 ROWS ONLY"
+        );
+        assert_eq!(
+            helpers::sanitize_structural(sql),
+            "SELECT TOP ? SUM(a) OVER (ORDER BY b ROWS BETWEEN ? PRECEDING AND ? FOLLOWING) FROM t TABLESAMPLE BERNOULLI (?) REPEATABLE (?) WHERE c = ? ORDER BY b OFFSET ? ROWS FETCH FIRST ? ROWS ONLY"
+        );
+    }
+
+    #[test]
+    fn test_structural_numbers_in_parentheses() {
+        let sql = "SELECT TOP (10) PERCENT a FROM t TABLESAMPLE SYSTEM (10 PERCENT) LIMIT 20";
+
+        assert_eq!(
+            sanitize_string(sql.to_string()),
+            "SELECT TOP (?) PERCENT a FROM t TABLESAMPLE SYSTEM (? PERCENT) LIMIT 20"
+        );
+        assert_eq!(
+            sanitize_string("SELECT * FROM t LIMIT (10)".to_string()),
+            "SELECT * FROM t LIMIT (10)"
+        );
+        assert_eq!(
+            helpers::sanitize_structural(sql),
+            "SELECT TOP (?) PERCENT a FROM t TABLESAMPLE SYSTEM (? PERCENT) LIMIT ?"
+        );
+    }
+
+    #[test]
+    fn test_paranoid_select_list() {
+        assert_eq!(
+            helpers::sanitize_paranoid(
+                "SELECT 'secret', 42, $$body$$, 0x1F, x'1F' FROM t WHERE a = 1 LIMIT 10 OFFSET 5",
+                &[]
+            ),
+            "SELECT ?, ?, ?, ?, x? FROM t WHERE a = ? LIMIT ? OFFSET ?"
+        );
+    }
+
+    #[test]
+    fn test_paranoid_hex_and_exponents() {
+        assert_eq!(
+            helpers::sanitize_paranoid(
+                "SELECT 0xDEADBEEF, 0xFF, 0x1234abcd, 1.5e10, 1e-5, 1.5E+10, 1abc FROM t",
+                &[]
+            ),
+            "SELECT ?, ?, ?, ?, ?, ?, ? FROM t"
+        );
+    }
+
+    #[test]
+    fn test_hex_and_exponents() {
+        assert_eq!(
+            sanitize_string(
+                "SELECT * FROM t WHERE a = 0x1234abcd AND b = 0xFF AND c = 1.5e10 AND d = 2E-3 AND e = 1.5E+10"
+                    .to_string()
+            ),
+            "SELECT * FROM t WHERE a = ? AND b = ? AND c = ? AND d = ? AND e = ?"
+        );
+        assert_eq!(
+            sanitize(lex("SELECT * FROM t WHERE a = 0xFF".to_string())).tokens[14],
+            Token::Placeholder(Placeholder::Redacted(ValueKind::Hex))
+        );
+    }
+
+    #[test]
+    fn test_non_ascii_after_number() {
+        assert_eq!(
+            sanitize_leaving_values("SELECT 1é".to_string()),
+            "SELECT 1é"
+        );
+        assert_eq!(
+            sanitize_string("SELECT * FROM t WHERE a = 1é AND b = 2eé".to_string()),
+            "SELECT * FROM t WHERE a = ? AND b = ?"
+        );
+    }
+
+    #[test]
+    fn test_paranoid_numeric_allowlist() {
+        assert_eq!(
+            helpers::sanitize_paranoid(
+                "SELECT TOP 5 'a' FROM t WHERE b = 1 LIMIT 10",
+                &["LIMIT", "top"]
+            ),
+            "SELECT TOP 5 ? FROM t WHERE b = ? LIMIT 10"
+        );
+    }
+
+    #[test]
+    fn test_paranoid_case_and_set() {
+        assert_eq!(
+            helpers::sanitize_paranoid(
+                "SELECT CASE WHEN a = 1 THEN 'secret' ELSE 'other' END FROM t; SET password = 'x'",
+                &[]
+            ),
+            "SELECT CASE WHEN a = ? THEN ? ELSE ? END FROM t; SET password = ?"
+        );
+    }
+
+    #[test]
+    fn test_paranoid_tab_separated() {
+        assert_eq!(
+            helpers::sanitize_paranoid("SELECT a\t'b'\t3 FROM t", &[]),
+            "SELECT a\t?\t? FROM t"
+        );
+    }
+
+    #[test]
+    fn test_credentials_mysql() {
+        assert_eq!(
+            sanitize_string("CREATE USER 'bob'@'%' IDENTIFIED BY 'pw'".to_string()),
+            "CREATE USER ?@? IDENTIFIED BY ?"
+        );
+        assert_eq!(
+            sanitize_string(
+                "ALTER USER bob IDENTIFIED WITH mysql_native_password BY 'pw'".to_string()
+            ),
+            "ALTER USER bob IDENTIFIED WITH mysql_native_password BY ?"
+        );
+        assert_eq!(
+            sanitize_string("SET PASSWORD = PASSWORD('pw')".to_string()),
+            "SET PASSWORD = PASSWORD(?)"
+        );
+        assert_eq!(
+            sanitize_string("SET PASSWORD FOR 'bob'@'localhost' = 'pw'".to_string()),
+            "SET PASSWORD FOR ?@? = ?"
+        );
+        assert_eq!(
+            sanitize_string("GRANT ALL ON db.* TO 'user'@'host'".to_string()),
+            "GRANT ALL ON db.* TO ?@?"
+        );
+        assert_eq!(
+            sanitize_string("CHANGE MASTER TO MASTER_PASSWORD='pw', MASTER_USER='u'".to_string()),
+            "CHANGE MASTER TO MASTER_PASSWORD=?, MASTER_USER=?"
+        );
+    }
+
+    #[test]
+    fn test_credentials_postgres() {
+        assert_eq!(
+            sanitize_string("ALTER ROLE x WITH PASSWORD 'pw'".to_string()),
+            "ALTER ROLE x WITH PASSWORD ?"
+        );
+        assert_eq!(
+            sanitize_string("ALTER USER bob WITH ENCRYPTED PASSWORD 'pw'".to_string()),
+            "ALTER USER bob WITH ENCRYPTED PASSWORD ?"
+        );
+        assert_eq!(
+            sanitize_leaving_values(
+                "CREATE ROLE r LOGIN PASSWORD 'pw' VALID UNTIL '2025-01-01'".to_string()
+            ),
+            "CREATE ROLE r LOGIN PASSWORD ? VALID UNTIL '2025-01-01'"
+        );
+        assert_eq!(
+            sanitize_string(
+                "CREATE SERVER s FOREIGN DATA WRAPPER postgres_fdw OPTIONS (password 'pw', host 'h')"
+                    .to_string()
+            ),
+            "CREATE SERVER s FOREIGN DATA WRAPPER postgres_fdw OPTIONS (password ?)"
+        );
+        assert_eq!(
+            sanitize_string(
+                "CREATE USER MAPPING FOR bob SERVER s OPTIONS (user 'bob', password 'pw')"
+                    .to_string()
+            ),
+            "CREATE USER MAPPING FOR bob SERVER s OPTIONS (user ?)"
+        );
+    }
+
+    #[test]
+    fn test_credentials_secrets_and_keys() {
+        assert_eq!(
+            sanitize_string("CREATE SECRET s (TYPE S3, KEY_ID 'k', SECRET 'x')".to_string()),
+            "CREATE SECRET s (TYPE S3, KEY_ID ?)"
+        );
+        assert_eq!(
+            sanitize_string("ALTER SYSTEM SET api_token TO 'abc'".to_string()),
+            "ALTER SYSTEM SET api_token TO ?"
+        );
+        assert_eq!(
+            sanitize_string("OPEN SYMMETRIC KEY k DECRYPTION BY PASSWORD = 'pw'".to_string()),
+            "OPEN SYMMETRIC KEY k DECRYPTION BY PASSWORD = ?"
+        );
+    }
+
+    #[test]
+    fn test_credentials_only_whole_keywords() {
+        assert_eq!(
+            sanitize_leaving_values("ALTER TABLE t ADD COLUMN api_key int DEFAULT 5".to_string()),
+            "ALTER TABLE t ADD COLUMN api_key int DEFAULT 5"
+        );
+        assert_eq!(
+            sanitize_leaving_values(
+                "SELECT CAST(api_key AS decimal(10, 2)), monkey 1, tokens 2, keys FROM t"
+                    .to_string()
+            ),
+            "SELECT CAST(api_key AS decimal(10, 2)), monkey 1, tokens 2, keys FROM t"
+        );
+    }
+
+    #[test]
+    fn test_set_postgres() {
+        assert_eq!(
+            sanitize_string("SET statement_timeout = 5000".to_string()),
+            "SET statement_timeout = ?"
+        );
+        assert_eq!(
+            sanitize_string("SET LOCAL app.user_id = '42'".to_string()),
+            "SET LOCAL app.user_id = ?"
+        );
+        assert_eq!(
+            sanitize_string("SET search_path TO 'tenant_123', \"public\"".to_string()),
+            "SET search_path TO ?, ?"
+        );
+        assert_eq!(
+            sanitize_string("SET TIME ZONE 'Europe/Amsterdam'".to_string()),
+            "SET TIME ZONE ?"
+        );
+        assert_eq!(
+            sanitize_string("SET SESSION AUTHORIZATION 'bob'; SET ROLE 'admin'".to_string()),
+            "SET SESSION AUTHORIZATION ?; SET ROLE ?"
+        );
+        assert_eq!(
+            sanitize_string("SELECT set_config('app.tenant', 'acme', false)".to_string()),
+            "SELECT set_config(?)"
+        );
+    }
+
+    #[test]
+    fn test_set_mysql() {
+        assert_eq!(
+            sanitize_string("SET @uid := 99".to_string()),
+            "SET @uid := ?"
+        );
+        assert_eq!(
+            sanitize_string("SET @a = 1, @b = 'x', @@session.time_zone = '+00:00'".to_string()),
+            "SET @a = ?, @b = ?, @@session.time_zone = ?"
+        );
+        assert_eq!(
+            sanitize_string("SET NAMES 'utf8mb4' COLLATE 'utf8mb4_unicode_ci'".to_string()),
+            "SET NAMES ? COLLATE ?"
+        );
+        assert_eq!(
+            sanitize_string("SET SESSION sql_mode = CONCAT(@@sql_mode, ',STRICT')".to_string()),
+            "SET SESSION sql_mode = CONCAT(@@sql_mode, ?)"
+        );
+    }
+
+    #[test]
+    fn test_set_sql_server() {
+        assert_eq!(
+            sanitize_string("DECLARE @x INT = 5; SET @x = 6; SET NOCOUNT ON".to_string()),
+            "DECLARE @x INT = ?; SET @x = ?; SET NOCOUNT ON"
+        );
+    }
+
+    #[test]
+    fn test_set_ends_with_statement() {
+        assert_eq!(
+            sanitize_leaving_values(
+                "SET application_name = 'app'; SELECT 1 FROM t LIMIT 1".to_string()
+            ),
+            "SET application_name = ?; SELECT 1 FROM t LIMIT 1"
+        );
+        assert_eq!(
+            sanitize_string("UPDATE t SET a = 'b' WHERE c = 1".to_string()),
+            "UPDATE t SET a = ? WHERE c = ?"
+        );
+    }
+
+    #[test]
+    fn test_update_assignments() {
+        assert_eq!(
+            sanitize_string(
+                "UPDATE `t` SET `a` = 'x', `b` = `b` + 1, `c` = NULL, `d` = TRUE WHERE `id` = 1"
+                    .to_string()
+            ),
+            "UPDATE `t` SET `a` = ?, `b` = `b` + ?, `c` = ?, `d` = ? WHERE `id` = ?"
+        );
+        assert_eq!(
+            sanitize_string(
+                "UPDATE \"t\" SET \"a\" = CONCAT(\"a\", 'x'), \"b\" = ARRAY['x', 'y']".to_string()
+            ),
+            "UPDATE \"t\" SET \"a\" = CONCAT(?), \"b\" = ARRAY[?]"
+        );
+        assert_eq!(
+            sanitize_string("UPDATE t SET (a, b) = (1, 'x') RETURNING id".to_string()),
+            "UPDATE t SET (a, b) = (?, ?) RETURNING id"
+        );
+        assert_eq!(
+            sanitize_string("UPDATE t SET a = (SELECT b FROM c WHERE d = 'e'), f = 1".to_string()),
+            "UPDATE t SET a = (SELECT b FROM c WHERE d = ?), f = ?"
+        );
+    }
+
+    #[test]
+    fn test_update_assignments_on_multiple_lines() {
+        assert_eq!(
+            sanitize_string("UPDATE t SET\n  a = 'x',\n  b =\n 2\nWHERE id = 3".to_string()),
+            "UPDATE t SET\n  a = ?,\n  b =\n ?\nWHERE id = ?"
+        );
+    }
+
+    #[test]
+    fn test_upsert_mysql() {
+        assert_eq!(
+            sanitize_string(
+                "INSERT INTO `t` (`a`, `b`) VALUES (1, 'x') ON DUPLICATE KEY UPDATE `a` = VALUES(`a`), `b` = 'x', `c` = `c` + 1"
+                    .to_string()
+            ),
+            "INSERT INTO `t` (`a`, `b`) VALUES (?, ?) ON DUPLICATE KEY UPDATE `a` = VALUES(`a`), `b` = ?, `c` = `c` + ?"
+        );
+        assert_eq!(
+            sanitize_string("INSERT INTO `t` SET `a` = 'x', `b` = 2".to_string()),
+            "INSERT INTO `t` SET `a` = ?, `b` = ?"
+        );
+    }
+
+    #[test]
+    fn test_upsert_postgres() {
+        assert_eq!(
+            sanitize_string(
+                "INSERT INTO t (id, b) VALUES (1, 'x') ON CONFLICT (id) DO UPDATE SET b = EXCLUDED.b || 'x', c = 2 WHERE t.d = 3"
+                    .to_string()
+            ),
+            "INSERT INTO t (id, b) VALUES (?, ?) ON CONFLICT (id) DO UPDATE SET b = EXCLUDED.b || ?, c = ? WHERE t.d = ?"
+        );
+        assert_eq!(
+            sanitize_string(
+                "INSERT INTO t (id) VALUES (1) ON CONFLICT ON CONSTRAINT t_pkey DO NOTHING"
+                    .to_string()
+            ),
+            "INSERT INTO t (id) VALUES (?) ON CONFLICT ON CONSTRAINT t_pkey DO NOTHING"
+        );
+    }
+
+    #[test]
+    fn test_merge() {
+        assert_eq!(
+            sanitize_string(
+                "MERGE INTO t USING s ON t.id = s.id WHEN MATCHED THEN UPDATE SET a = 'x', b = 2 WHEN NOT MATCHED THEN INSERT (id, a) VALUES (s.id, 'y')"
+                    .to_string()
+            ),
+            "MERGE INTO t USING s ON t.id = s.id WHEN MATCHED THEN UPDATE SET a = ?, b = ? WHEN NOT MATCHED THEN INSERT (id, a) VALUES (s.id, ?)"
+        );
+        assert_eq!(
+            sanitize_string(
+                "MERGE INTO t USING (SELECT id FROM u WHERE v = 1) s ON t.id = s.id AND t.kind = 'k' WHEN MATCHED AND t.a > 5 THEN DELETE"
+                    .to_string()
+            ),
+            "MERGE INTO t USING (SELECT id FROM u WHERE v = ?) s ON t.id = s.id AND t.kind = ? WHEN MATCHED AND t.a > ? THEN DELETE"
+        );
+    }
+
+    #[test]
+    fn test_truncated_single_quote_in_select() {
+        assert_eq!(
+            helpers::sanitize_truncated("SELECT 'sec"),
+            "SELECT ? ...TRUNCATED"
+        );
+    }
+
+    #[test]
+    fn test_truncated_in_list() {
+        assert_eq!(
+            helpers::sanitize_truncated("SELECT * FROM `table` WHERE `id` IN (1, 2, 3"),
+            "SELECT * FROM `table` WHERE `id` IN (?) ...TRUNCATED"
+        );
+    }
+
+    #[test]
+    fn test_truncated_in_list_after_comma() {
+        assert_eq!(
+            helpers::sanitize_truncated("SELECT * FROM `table` WHERE `id` IN (1, 2, "),
+            "SELECT * FROM `table` WHERE `id` IN (?) ...TRUNCATED"
+        );
+    }
+
+    #[test]
+    fn test_truncated_in_nested_function() {
+        assert_eq!(
+            helpers::sanitize_truncated("SELECT * FROM `table` WHERE `name` = LOWER(UPPER('sec"),
+            "SELECT * FROM `table` WHERE `name` = LOWER(UPPER(?)) ...TRUNCATED"
+        );
+    }
+
+    #[test]
+    fn test_truncated_array() {
+        assert_eq!(
+            helpers::sanitize_truncated("SELECT * FROM \"table\" WHERE \"field\" = ARRAY['a','b"),
+            "SELECT * FROM \"table\" WHERE ? = ARRAY[?] ...TRUNCATED"
+        );
+    }
+
+    #[test]
+    fn test_truncated_after_operator() {
+        assert_eq!(
+            helpers::sanitize_truncated("SELECT * FROM `table` WHERE `id` ="),
+            "SELECT * FROM `table` WHERE `id` = ? ...TRUNCATED"
+        );
+    }
+
+    #[test]
+    fn test_truncated_insert_values() {
+        assert_eq!(
+            helpers::sanitize_truncated(
+                "INSERT INTO `table` (`field1`, `field2`) VALUES ('value', 1), ('val"
+            ),
+            "INSERT INTO `table` (`field1`, `field2`) VALUES (?, ?), ... ...TRUNCATED"
+        );
+    }
+
+    #[test]
+    fn test_truncated_comment() {
+        assert_eq!(
+            helpers::sanitize_truncated("SELECT * FROM `table` /* trace: a1b2"),
+            "SELECT * FROM `table` ...TRUNCATED"
+        );
+    }
+
+    #[test]
+    fn test_not_truncated() {
+        let sql = "SELECT * FROM `table` WHERE `id` IN (?) LIMIT 1;";
+
+        assert_eq!(helpers::sanitize_truncated(sql), sql);
+    }
+
+    #[test]
+    fn test_truncated_without_option() {
+        assert_eq!(
+            sanitize_string("SELECT * FROM `table` WHERE `id` IN (1, 2, 3".to_string()),
+            "SELECT * FROM `table` WHERE `id` IN (?"
+        );
+    }
+
+    mod helpers {
+        use super::super::super::{lex, sanitize_with_options, write};
+        use super::SanitizeOptions;
+
+        pub fn sanitize_truncated(sql: &str) -> String {
+            let options = SanitizeOptions {
+                detect_truncation: true,
+                ..Default::default()
+            };
+            write(sanitize_with_options(lex(sql.to_string()), &options))
+        }
+
+        pub fn sanitize_paranoid(sql: &str, numeric_allowlist: &[&str]) -> String {
+            let options = SanitizeOptions {
+                numeric_allowlist: numeric_allowlist
+                    .iter()
+                    .map(|keyword| keyword.to_string())
+                    .collect(),
+                ..paranoid()
+            };
+            write(sanitize_with_options(lex(sql.to_string()), &options))
+        }
+
+        pub fn paranoid() -> SanitizeOptions {
+            SanitizeOptions {
+                paranoid: true,
+                ..Default::default()
+            }
+        }
+
+        pub fn sanitize_structural(sql: &str) -> String {
+            let options = SanitizeOptions {
+                redact_structural_numbers: true,
+                ..Default::default()
+            };
+            write(sanitize_with_options(lex(sql.to_string()), &options))
+        }
+
+        pub fn sanitize_bodies(sql: &str) -> String {
+            let options = SanitizeOptions {
+                sanitize_bodies: true,
+                ..Default::default()
+            };
+            write(sanitize_with_options(lex(sql.to_string()), &options))
+        }
+
+        pub fn sanitize_bucketed(sql: &str) -> String {
+            let options = SanitizeOptions {
+                bucket_lists: true,
+                ..Default::default()
+            };
+            write(sanitize_with_options(lex(sql.to_string()), &options))
+        }
+    }
 }
@@ -0,0 +1,549 @@
+use super::{Keyword, Sql, Token};
+
+/// The tokens of a single statement in a script, as positions in
+/// `Sql::tokens`. Leading and trailing whitespace and the semicolon that
+/// ends the statement are not included.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StatementRange {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl StatementRange {
+    /// The kind of the statement in this range
+    pub fn kind(&self, sql: &Sql) -> StatementKind {
+        kind(sql, self.start, self.end)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StatementKind {
+    Select,
+    Insert,
+    Update,
+    Delete,
+    // INSERT ... ON CONFLICT, INSERT ... ON DUPLICATE KEY UPDATE, REPLACE and MERGE
+    Upsert,
+    Ddl(DdlKind),
+    Transaction(TransactionKind),
+    Set,
+    Show,
+    Explain,
+    Call,
+    // COPY and LOAD DATA
+    Copy,
+    Other,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DdlKind {
+    Create,
+    Alter,
+    Drop,
+    Truncate,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TransactionKind {
+    Begin,    // BEGIN, START TRANSACTION
+    Commit,   // COMMIT, END
+    Rollback, // ROLLBACK, ABORT
+    Savepoint,
+}
+
+// Classifies the statement in the tokens from `start` to `end` by its first
+// significant keyword. Comments, parentheses around a select and the common
+// table expressions of a WITH clause are skipped.
+pub fn kind(sql: &Sql, start: usize, end: usize) -> StatementKind {
+    let end = end.min(sql.tokens.len());
+    let mut positions = (start..end).filter(|pos| is_significant(&sql.tokens[*pos]));
+
+    let mut first = loop {
+        match positions.next() {
+            Some(pos) if sql.tokens[pos] == Token::ParentheseOpen => (),
+            Some(pos) => break pos,
+            None => return StatementKind::Other,
+        }
+    };
+
+    if sql.is_keyword(&sql.tokens[first], "with") {
+        // The statement continues after the last common table expression,
+        // those are all wrapped in parentheses.
+        let mut depth = 0;
+        first = loop {
+            let pos = match positions.next() {
+                Some(pos) => pos,
+                None => return StatementKind::Other,
+            };
+            match &sql.tokens[pos] {
+                Token::ParentheseOpen => depth += 1,
+                Token::ParentheseClose => depth -= 1,
+                Token::Keyword(
+                    Keyword::Select | Keyword::Insert | Keyword::Update | Keyword::Other(_),
+                ) if depth == 0 && starts_statement(sql, pos) => break pos,
+                _ => (),
+            }
+        };
+    }
+
+    let token = &sql.tokens[first];
+    match token {
+        Token::Keyword(Keyword::Select) => StatementKind::Select,
+        Token::Keyword(Keyword::Update) => StatementKind::Update,
+        Token::Keyword(Keyword::Set) => StatementKind::Set,
+        Token::Keyword(Keyword::Insert) => {
+            if is_upsert(sql, first, end) {
+                StatementKind::Upsert
+            } else {
+                StatementKind::Insert
+            }
+        }
+        token if sql.is_keyword(token, "delete") => StatementKind::Delete,
+        token if sql.is_keyword(token, "replace") || sql.is_keyword(token, "merge") => {
+            StatementKind::Upsert
+        }
+        token if sql.is_keyword(token, "create") => StatementKind::Ddl(DdlKind::Create),
+        token if sql.is_keyword(token, "alter") => StatementKind::Ddl(DdlKind::Alter),
+        token if sql.is_keyword(token, "drop") => StatementKind::Ddl(DdlKind::Drop),
+        token if sql.is_keyword(token, "truncate") => StatementKind::Ddl(DdlKind::Truncate),
+        token if sql.is_keyword(token, "begin") => {
+            if starts_block(sql, first) {
+                StatementKind::Other
+            } else {
+                StatementKind::Transaction(TransactionKind::Begin)
+            }
+        }
+        token if sql.is_keyword(token, "start") => {
+            StatementKind::Transaction(TransactionKind::Begin)
+        }
+        token if sql.is_keyword(token, "commit") || sql.is_keyword(token, "end") => {
+            StatementKind::Transaction(TransactionKind::Commit)
+        }
+        token if sql.is_keyword(token, "rollback") || sql.is_keyword(token, "abort") => {
+            StatementKind::Transaction(TransactionKind::Rollback)
+        }
+        token if sql.is_keyword(token, "savepoint") || sql.is_keyword(token, "release") => {
+            StatementKind::Transaction(TransactionKind::Savepoint)
+        }
+        token if sql.is_keyword(token, "reset") => StatementKind::Set,
+        token if sql.is_keyword(token, "show") => StatementKind::Show,
+        token
+            if sql.is_keyword(token, "explain")
+                || sql.is_keyword(token, "describe")
+                || sql.is_keyword(token, "desc") =>
+        {
+            StatementKind::Explain
+        }
+        token
+            if sql.is_keyword(token, "call")
+                || sql.is_keyword(token, "exec")
+                || sql.is_keyword(token, "execute") =>
+        {
+            StatementKind::Call
+        }
+        token if sql.is_keyword(token, "copy") => StatementKind::Copy,
+        token if sql.is_keyword(token, "load") => StatementKind::Copy,
+        _ => StatementKind::Other,
+    }
+}
+
+pub(crate) fn is_significant(token: &Token) -> bool {
+    !is_whitespace(token) && !matches!(token, Token::Comment(_))
+}
+
+// Whether the keyword at `pos` starts the statement that follows the common
+// table expressions of a WITH clause.
+fn starts_statement(sql: &Sql, pos: usize) -> bool {
+    match &sql.tokens[pos] {
+        Token::Keyword(Keyword::Select | Keyword::Insert | Keyword::Update) => true,
+        token => sql.is_keyword(token, "delete") || sql.is_keyword(token, "merge"),
+    }
+}
+
+// INSERT ... ON CONFLICT, INSERT ... ON DUPLICATE KEY and INSERT OR REPLACE
+fn is_upsert(sql: &Sql, start: usize, end: usize) -> bool {
+    (start..end).any(|pos| match &sql.tokens[pos] {
+        Token::Keyword(Keyword::On) => next_significant(sql, pos).is_some_and(|(_, token)| {
+            sql.is_keyword(token, "conflict") || sql.is_keyword(token, "duplicate")
+        }),
+        Token::Keyword(Keyword::Or) => {
+            next_significant(sql, pos).is_some_and(|(_, token)| sql.is_keyword(token, "replace"))
+        }
+        _ => false,
+    })
+}
+
+pub fn split(sql: &Sql) -> Vec<StatementRange> {
+    let mut ranges = Vec::new();
+    let mut start = 0;
+    // Depth of BEGIN ... END blocks, semicolons within them don't split
+    let mut blocks = 0;
+    // Depth of CASE ... END expressions, so their END doesn't close a block
+    let mut cases = 0;
+
+    for (pos, token) in sql.tokens.iter().enumerate() {
+        match token {
+            Token::Semicolon if blocks == 0 => {
+                push_range(sql, &mut ranges, start, pos);
+                start = pos + 1;
+            }
+            token if sql.is_keyword(token, "begin") && starts_block(sql, pos) => blocks += 1,
+            token if sql.is_keyword(token, "case") && !follows_end(sql, pos) => cases += 1,
+            token if sql.is_keyword(token, "end") => {
+                let closes = next_significant(sql, pos).map(|(_, token)| token);
+                let closes_control_flow = ["if", "loop", "while", "repeat", "for"]
+                    .iter()
+                    .any(|keyword| closes.is_some_and(|token| sql.is_keyword(token, keyword)));
+                if closes_control_flow {
+                    // The opening IF, LOOP, ... isn't tracked
+                } else if cases > 0 {
+                    cases -= 1;
+                } else if blocks > 0 {
+                    blocks -= 1;
+                }
+            }
+            _ => (),
+        }
+    }
+    push_range(sql, &mut ranges, start, sql.tokens.len());
+
+    ranges
+}
+
+// Adds the range without surrounding whitespace if it contains anything
+// other than whitespace and comments.
+fn push_range(sql: &Sql, ranges: &mut Vec<StatementRange>, start: usize, end: usize) {
+    let tokens = &sql.tokens[start..end];
+    let content = |token: &Token| !is_whitespace(token);
+    let has_statement = tokens
+        .iter()
+        .any(|token| !matches!(token, Token::Comment(_)) && content(token));
+    if !has_statement {
+        return;
+    }
+
+    let leading = tokens.iter().position(content).unwrap_or(0);
+    let trailing = tokens.iter().rev().position(content).unwrap_or(0);
+    ranges.push(StatementRange {
+        start: start + leading,
+        end: end - trailing,
+    });
+}
+
+fn is_whitespace(token: &Token) -> bool {
+    matches!(token, Token::Space | Token::Newline | Token::None)
+}
+
+// Returns the next token after `pos` that isn't whitespace or a comment
+fn next_significant(sql: &Sql, pos: usize) -> Option<(usize, &Token)> {
+    sql.tokens
+        .iter()
+        .enumerate()
+        .skip(pos + 1)
+        .find(|(_, token)| !is_whitespace(token) && !matches!(token, Token::Comment(_)))
+}
+
+// BEGIN on its own, or followed by one of the transaction keywords starts a
+// transaction. Otherwise it starts a block in a procedure, trigger or
+// compound statement.
+fn starts_block(sql: &Sql, pos: usize) -> bool {
+    match next_significant(sql, pos) {
+        None | Some((_, Token::Semicolon)) => false,
+        Some((_, token)) => ![
+            "transaction",
+            "work",
+            "tran",
+            "isolation",
+            "read",
+            "deferred",
+            "immediate",
+            "exclusive",
+        ]
+        .iter()
+        .any(|keyword| sql.is_keyword(token, keyword)),
+    }
+}
+
+// Whether the token at `pos` is preceded by END, as in `END CASE`
+fn follows_end(sql: &Sql, pos: usize) -> bool {
+    sql.tokens[..pos]
+        .iter()
+        .rev()
+        .find(|token| !is_whitespace(token) && !matches!(token, Token::Comment(_)))
+        .is_some_and(|token| sql.is_keyword(token, "end"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{
+        lex, sanitize, sanitize_each, split_statements, statement_kind, write, Sql,
+    };
+    use super::{DdlKind, StatementKind, StatementRange, TransactionKind};
+
+    fn kind(sql: &str) -> StatementKind {
+        statement_kind(&lex(sql.to_string()))
+    }
+
+    fn statements(sql: &str) -> Vec<String> {
+        let sql = lex(sql.to_string());
+        let ranges = split_statements(&sql);
+        let Sql { buf, tokens, .. } = sql;
+        let mut tokens: Vec<_> = tokens.into_iter().map(Some).collect();
+        ranges
+            .iter()
+            .map(|range| {
+                let statement = Sql {
+                    buf: buf.clone(),
+                    tokens: tokens[range.start..range.end]
+                        .iter_mut()
+                        .map(|token| token.take().unwrap())
+                        .collect(),
+                    diagnostics: Vec::new(),
+                };
+                write(statement)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_single_statement() {
+        let sql = lex("SELECT * FROM `table`".to_string());
+
+        assert_eq!(
+            split_statements(&sql),
+            vec![StatementRange { start: 0, end: 7 }]
+        );
+    }
+
+    #[test]
+    fn test_empty() {
+        assert_eq!(split_statements(&lex("".to_string())), vec![]);
+        assert_eq!(split_statements(&lex(" ; ;\n".to_string())), vec![]);
+    }
+
+    #[test]
+    fn test_transaction() {
+        assert_eq!(
+            statements(
+                "BEGIN; UPDATE `table` SET `a` = 1;\nINSERT INTO `table` VALUES (1); COMMIT;"
+            ),
+            vec![
+                "BEGIN",
+                "UPDATE `table` SET `a` = 1",
+                "INSERT INTO `table` VALUES (1)",
+                "COMMIT"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_begin_transaction() {
+        assert_eq!(
+            statements("BEGIN TRANSACTION; SELECT 1; END;"),
+            vec!["BEGIN TRANSACTION", "SELECT 1", "END"]
+        );
+    }
+
+    #[test]
+    fn test_semicolons_in_quotes() {
+        assert_eq!(
+            statements("SELECT 'a;b', \"c;d\", `e;f`; SELECT $$g;h$$; SELECT $body$i;j$body$"),
+            vec![
+                "SELECT 'a;b', \"c;d\", `e;f`",
+                "SELECT $$g;h$$",
+                "SELECT $body$i;j$body$"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_semicolons_in_comments() {
+        assert_eq!(
+            statements("SELECT 1 /* a; b */; -- c; d\nSELECT 2;"),
+            vec!["SELECT 1 /* a; b */", "-- c; d\nSELECT 2"]
+        );
+    }
+
+    #[test]
+    fn test_only_comment_after_last_statement() {
+        assert_eq!(statements("SELECT 1; -- done"), vec!["SELECT 1"]);
+    }
+
+    #[test]
+    fn test_begin_end_block() {
+        assert_eq!(
+            statements(
+                "CREATE TRIGGER t AFTER INSERT ON a FOR EACH ROW BEGIN UPDATE b SET c = 1; IF d THEN SELECT 1; END IF; SELECT CASE WHEN e THEN 1 END; END; SELECT 2"
+            ),
+            vec![
+                "CREATE TRIGGER t AFTER INSERT ON a FOR EACH ROW BEGIN UPDATE b SET c = 1; IF d THEN SELECT 1; END IF; SELECT CASE WHEN e THEN 1 END; END",
+                "SELECT 2"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_sanitize_each() {
+        assert_eq!(
+            sanitize_each(
+                "BEGIN; UPDATE `table` SET `a` = 'secret' WHERE `id` IN (1, 2);\nINSERT INTO `table` (`a`) VALUES ('x'), ('y'); COMMIT;"
+                    .to_string()
+            ),
+            vec![
+                "BEGIN",
+                "UPDATE `table` SET `a` = ? WHERE `id` IN (?)",
+                "INSERT INTO `table` (`a`) VALUES (?), ...",
+                "COMMIT"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_sanitize_each_state_does_not_leak() {
+        assert_eq!(
+            sanitize_each("SELECT COUNT(1; SELECT 'a'".to_string()),
+            vec!["SELECT COUNT(?", "SELECT 'a'"]
+        );
+        assert_eq!(
+            sanitize_each("SELECT f(1; SELECT 'a')".to_string()),
+            vec!["SELECT f(?", "SELECT 'a')"]
+        );
+        assert_eq!(
+            sanitize_each("INSERT INTO t VALUES (1), (2; SELECT 'a'".to_string()),
+            vec!["INSERT INTO t VALUES (?), ...", "SELECT 'a'"]
+        );
+    }
+
+    #[test]
+    fn test_kind_dml() {
+        assert_eq!(kind("SELECT * FROM `table`"), StatementKind::Select);
+        assert_eq!(kind("select 1"), StatementKind::Select);
+        assert_eq!(
+            kind("INSERT INTO `table` VALUES (1)"),
+            StatementKind::Insert
+        );
+        assert_eq!(kind("UPDATE `table` SET `a` = 1"), StatementKind::Update);
+        assert_eq!(kind("DELETE FROM `table`"), StatementKind::Delete);
+    }
+
+    #[test]
+    fn test_kind_upsert() {
+        assert_eq!(
+            kind("INSERT INTO t (a) VALUES (1) ON CONFLICT (a) DO NOTHING"),
+            StatementKind::Upsert
+        );
+        assert_eq!(
+            kind("INSERT INTO t (a) VALUES (1) ON DUPLICATE KEY UPDATE a = VALUES(a)"),
+            StatementKind::Upsert
+        );
+        assert_eq!(
+            kind("INSERT OR REPLACE INTO t VALUES (1)"),
+            StatementKind::Upsert
+        );
+        assert_eq!(kind("REPLACE INTO t VALUES (1)"), StatementKind::Upsert);
+        assert_eq!(
+            kind("MERGE INTO t USING s ON t.id = s.id WHEN MATCHED THEN DELETE"),
+            StatementKind::Upsert
+        );
+    }
+
+    #[test]
+    fn test_kind_skips_comments_and_parentheses() {
+        assert_eq!(
+            kind("/* controller: users */ -- action\n (SELECT 1) UNION (SELECT 2)"),
+            StatementKind::Select
+        );
+    }
+
+    #[test]
+    fn test_kind_with() {
+        assert_eq!(
+            kind("WITH a AS (SELECT 1), b (x) AS (DELETE FROM c RETURNING x) SELECT * FROM a, b"),
+            StatementKind::Select
+        );
+        assert_eq!(
+            kind("WITH RECURSIVE a AS (SELECT 1) INSERT INTO t SELECT * FROM a ON CONFLICT DO NOTHING"),
+            StatementKind::Upsert
+        );
+        assert_eq!(
+            kind("WITH a AS MATERIALIZED (SELECT 1) UPDATE t SET b = 1"),
+            StatementKind::Update
+        );
+        assert_eq!(kind("WITH a AS (SELECT 1)"), StatementKind::Other);
+    }
+
+    #[test]
+    fn test_kind_ddl() {
+        assert_eq!(
+            kind("CREATE TABLE t (a int)"),
+            StatementKind::Ddl(DdlKind::Create)
+        );
+        assert_eq!(
+            kind("ALTER TABLE t ADD b int"),
+            StatementKind::Ddl(DdlKind::Alter)
+        );
+        assert_eq!(kind("DROP INDEX i"), StatementKind::Ddl(DdlKind::Drop));
+        assert_eq!(kind("TRUNCATE t"), StatementKind::Ddl(DdlKind::Truncate));
+    }
+
+    #[test]
+    fn test_kind_transaction() {
+        let begin = StatementKind::Transaction(TransactionKind::Begin);
+        let commit = StatementKind::Transaction(TransactionKind::Commit);
+        let rollback = StatementKind::Transaction(TransactionKind::Rollback);
+        let savepoint = StatementKind::Transaction(TransactionKind::Savepoint);
+
+        assert_eq!(kind("BEGIN"), begin);
+        assert_eq!(kind("BEGIN;"), begin);
+        assert_eq!(kind("BEGIN ISOLATION LEVEL SERIALIZABLE"), begin);
+        assert_eq!(kind("START TRANSACTION"), begin);
+        assert_eq!(kind("COMMIT"), commit);
+        assert_eq!(kind("END"), commit);
+        assert_eq!(kind("ROLLBACK TO SAVEPOINT s1"), rollback);
+        assert_eq!(kind("SAVEPOINT s1"), savepoint);
+        assert_eq!(kind("RELEASE SAVEPOINT s1"), savepoint);
+        assert_eq!(kind("BEGIN SELECT 1; END"), StatementKind::Other);
+    }
+
+    #[test]
+    fn test_kind_other_statements() {
+        assert_eq!(kind("SET statement_timeout = 5000"), StatementKind::Set);
+        assert_eq!(kind("RESET ALL"), StatementKind::Set);
+        assert_eq!(kind("SHOW TABLES"), StatementKind::Show);
+        assert_eq!(kind("EXPLAIN ANALYZE SELECT 1"), StatementKind::Explain);
+        assert_eq!(kind("DESCRIBE t"), StatementKind::Explain);
+        assert_eq!(kind("CALL p(1)"), StatementKind::Call);
+        assert_eq!(kind("COPY t FROM STDIN"), StatementKind::Copy);
+        assert_eq!(
+            kind("LOAD DATA INFILE '/tmp/x' INTO TABLE t"),
+            StatementKind::Copy
+        );
+        assert_eq!(kind("VACUUM"), StatementKind::Other);
+        assert_eq!(kind(""), StatementKind::Other);
+    }
+
+    #[test]
+    fn test_kind_sanitized() {
+        let sql = sanitize(lex("/* comment */ DELETE FROM t WHERE id = 1".to_string()));
+
+        assert_eq!(statement_kind(&sql), StatementKind::Delete);
+    }
+
+    #[test]
+    fn test_kind_of_each_statement() {
+        let sql = lex("BEGIN; UPDATE t SET a = 1; COMMIT".to_string());
+        let kinds: Vec<StatementKind> = split_statements(&sql)
+            .iter()
+            .map(|range| range.kind(&sql))
+            .collect();
+
+        assert_eq!(
+            kinds,
+            vec![
+                StatementKind::Transaction(TransactionKind::Begin),
+                StatementKind::Update,
+                StatementKind::Transaction(TransactionKind::Commit),
+            ]
+        );
+    }
+}
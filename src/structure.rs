@@ -0,0 +1,563 @@
+use std::ops::Range;
+
+use super::statements::{self, is_significant};
+use super::{Keyword, LiteralValueTypeIndicator, LogicalOperator, Operator, Sql, Token, TokenTree};
+
+/// The common table expressions, subqueries and tables of the statements
+/// in a `Sql` struct.
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueryStructure {
+    pub ctes: Vec<Cte>,
+    /// Every statement and every subquery, in the order they start in
+    pub scopes: Vec<Scope>,
+    /// Zero if there are no subqueries or common table expressions
+    pub max_subquery_depth: usize,
+}
+
+/// A common table expression, `name AS (...)` in a `WITH` clause.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cte {
+    pub name: String,
+    /// Positions in `Sql::tokens` from the name up to and including the
+    /// closing parenthese.
+    pub span: Range<usize>,
+    /// Tables read in the body of the expression, including those read in
+    /// its subqueries.
+    pub tables: Vec<String>,
+}
+
+/// A statement or a subquery within it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Scope {
+    /// Statements have a depth of 0, every subquery adds one
+    pub depth: usize,
+    /// Positions in `Sql::tokens` of the statement or of the subquery within
+    /// its parentheses.
+    pub span: Range<usize>,
+    /// Tables referenced directly in this scope, not in its subqueries
+    pub tables: Vec<String>,
+}
+
+// Keywords that can follow a table name but are not an alias for it
+const NOT_ALIASES: &[&str] = &[
+    "cross",
+    "default",
+    "except",
+    "fetch",
+    "for",
+    "full",
+    "group",
+    "having",
+    "intersect",
+    "left",
+    "lock",
+    "natural",
+    "order",
+    "outer",
+    "returning",
+    "right",
+    "straight_join",
+    "union",
+    "using",
+    "window",
+    "with",
+];
+
+// Keywords that end the list of tables after a FROM
+const END_OF_FROM: &[&str] = &[
+    "except",
+    "fetch",
+    "for",
+    "group",
+    "having",
+    "intersect",
+    "lock",
+    "order",
+    "returning",
+    "union",
+    "window",
+];
+
+pub fn structure(sql: &Sql) -> QueryStructure {
+    let positions: Vec<usize> = (0..sql.tokens.len())
+        .filter(|pos| is_significant(&sql.tokens[*pos]))
+        .collect();
+    let mut analyzer = Analyzer {
+        sql,
+        tree: TokenTree::new(sql),
+        positions: &positions,
+        ctes: Vec::new(),
+        scopes: Vec::new(),
+    };
+
+    for range in statements::split(sql) {
+        let start = positions.partition_point(|pos| *pos < range.start);
+        let end = positions.partition_point(|pos| *pos < range.end);
+        if start < end {
+            analyzer.walk(start, end);
+        }
+    }
+
+    let max_subquery_depth = analyzer
+        .scopes
+        .iter()
+        .map(|scope| scope.depth)
+        .max()
+        .unwrap_or(0);
+    QueryStructure {
+        ctes: analyzer.ctes,
+        scopes: analyzer.scopes,
+        max_subquery_depth,
+    }
+}
+
+// Walks the significant tokens, `i`, `start` and `end` are indexes in
+// `positions` which are positions in `Sql::tokens`.
+struct Analyzer<'a> {
+    sql: &'a Sql,
+    tree: TokenTree,
+    positions: &'a [usize],
+    ctes: Vec<Cte>,
+    scopes: Vec<Scope>,
+}
+
+// A statement or subquery that is being walked
+struct ScopeFrame {
+    // Index in `Analyzer::scopes`
+    index: usize,
+    group: Option<usize>,
+    // Whether a comma continues the list of tables after a FROM, such as
+    // in `FROM a JOIN b ON a.id = b.a_id, c`
+    in_from: bool,
+    i: usize,
+    end: usize,
+    depth: usize,
+}
+
+// A WITH clause that is being walked
+struct CtesFrame {
+    i: usize,
+    end: usize,
+    depth: usize,
+    // Index in `Analyzer::ctes` of the expression whose body is being
+    // walked and index of the first scope in that body
+    body: Option<(usize, usize)>,
+}
+
+enum Frame {
+    Scope(ScopeFrame),
+    Ctes(CtesFrame),
+}
+
+enum Step {
+    // Walk a nested frame before continuing with this one
+    Enter(Frame),
+    // Done with this frame, the enclosing scope continues at the index
+    Exit(usize),
+}
+
+impl<'a> Analyzer<'a> {
+    fn get(&self, i: usize) -> Option<&'a Token> {
+        self.positions.get(i).map(|pos| &self.sql.tokens[*pos])
+    }
+
+    fn keyword_at(&self, i: usize, keyword: &str) -> bool {
+        self.get(i)
+            .is_some_and(|token| self.sql.is_keyword(token, keyword))
+    }
+
+    // Index of the token that closes the group opened at `i`, or `end` if
+    // it is never closed.
+    fn closing(&self, i: usize, end: usize) -> usize {
+        self.tree
+            .closing(self.positions[i])
+            .and_then(|close| self.positions.binary_search(&close).ok())
+            .map_or(end, |close| close.min(end))
+    }
+
+    fn span(&self, start: usize, end: usize) -> Range<usize> {
+        self.positions[start]..self.positions[end - 1] + 1
+    }
+
+    // Walks the statement from `start` to `end` and everything nested in
+    // it. Keeps its own stack of frames instead of recursing, so deeply
+    // nested subqueries can't overflow the call stack.
+    fn walk(&mut self, start: usize, end: usize) {
+        let mut stack = vec![self.enter_scope(start, end, 0)];
+        while let Some(frame) = stack.last_mut() {
+            let step = match frame {
+                Frame::Scope(frame) => self.scope(frame),
+                Frame::Ctes(frame) => self.ctes(frame),
+            };
+            match step {
+                Step::Enter(frame) => stack.push(frame),
+                Step::Exit(i) => {
+                    stack.pop();
+                    if let Some(Frame::Scope(frame)) = stack.last_mut() {
+                        frame.i = i;
+                    }
+                }
+            }
+        }
+    }
+
+    fn enter_scope(&mut self, start: usize, end: usize, depth: usize) -> Frame {
+        let index = self.scopes.len();
+        self.scopes.push(Scope {
+            depth,
+            span: self.span(start, end),
+            tables: Vec::new(),
+        });
+        Frame::Scope(ScopeFrame {
+            index,
+            group: self.tree.enclosing(self.positions[start]),
+            in_from: false,
+            i: start,
+            end,
+            depth,
+        })
+    }
+
+    fn scope(&mut self, frame: &mut ScopeFrame) -> Step {
+        let (index, group, end) = (frame.index, frame.group, frame.end);
+
+        while frame.i < end {
+            let i = frame.i;
+            match self.get(i) {
+                Some(Token::Comma)
+                    if frame.in_from && self.tree.enclosing(self.positions[i]) == group =>
+                {
+                    frame.i = self.tables(index, i + 1, end, true);
+                    continue;
+                }
+                Some(Token::ParentheseOpen) => {
+                    let close = self.closing(i, end);
+                    let subquery = matches!(self.get(i + 1), Some(Token::Keyword(Keyword::Select)))
+                        || self.keyword_at(i + 1, "with");
+                    if subquery && i + 1 < close {
+                        return Step::Enter(self.enter_scope(i + 1, close, frame.depth + 1));
+                    }
+                }
+                // Not the FROM in a function call like EXTRACT(YEAR FROM date)
+                Some(Token::Keyword(Keyword::From))
+                    if self.tree.enclosing(self.positions[i]) == group =>
+                {
+                    frame.in_from = true;
+                    frame.i = self.tables(index, i + 1, end, true);
+                    continue;
+                }
+                Some(Token::Keyword(Keyword::Join | Keyword::Into | Keyword::Update)) => {
+                    frame.i = self.tables(index, i + 1, end, false);
+                    continue;
+                }
+                Some(Token::Keyword(
+                    Keyword::Select
+                    | Keyword::Where
+                    | Keyword::Set
+                    | Keyword::Values
+                    | Keyword::Limit
+                    | Keyword::Offset,
+                )) => frame.in_from = false,
+                Some(token) if self.sql.is_keyword(token, "with") => {
+                    let mut i = i + 1;
+                    if self.keyword_at(i, "recursive") {
+                        i += 1;
+                    }
+                    return Step::Enter(Frame::Ctes(CtesFrame {
+                        i,
+                        end,
+                        depth: frame.depth,
+                        body: None,
+                    }));
+                }
+                Some(token)
+                    if END_OF_FROM
+                        .iter()
+                        .any(|keyword| self.sql.is_keyword(token, keyword)) =>
+                {
+                    frame.in_from = false
+                }
+                _ => (),
+            }
+            frame.i += 1;
+        }
+
+        // The enclosing scope continues after the closing parenthese
+        Step::Exit(end + 1)
+    }
+
+    // Reads the common table expressions of a WITH clause and exits with
+    // the index after them.
+    fn ctes(&mut self, frame: &mut CtesFrame) -> Step {
+        let end = frame.end;
+        let mut i = frame.i;
+
+        // Back from the body of an expression
+        if let Some((index, first_scope)) = frame.body.take() {
+            for scope in first_scope..self.scopes.len() {
+                for table in self.scopes[scope].tables.clone() {
+                    push_unique(&mut self.ctes[index].tables, table);
+                }
+            }
+            if self.get(i) != Some(&Token::Comma) {
+                return Step::Exit(i);
+            }
+            i += 1;
+        }
+
+        while let Some(name) = self.name(i) {
+            let name_i = i;
+            i += 1;
+            // Column names
+            if self.get(i) == Some(&Token::ParentheseOpen) {
+                i = self.closing(i, end) + 1;
+            }
+            if !self.keyword_at(i, "as") {
+                break;
+            }
+            i += 1;
+            if self.get(i) == Some(&Token::Operator(Operator::Logical(LogicalOperator::Not))) {
+                i += 1;
+            }
+            if self.keyword_at(i, "materialized") {
+                i += 1;
+            }
+            if self.get(i) != Some(&Token::ParentheseOpen) {
+                break;
+            }
+
+            let close = self.closing(i, end);
+            let index = self.ctes.len();
+            self.ctes.push(Cte {
+                name,
+                span: self.span(name_i, (close + 1).min(end)),
+                tables: Vec::new(),
+            });
+            if i + 1 < close {
+                frame.i = close + 1;
+                frame.body = Some((index, self.scopes.len()));
+                return Step::Enter(self.enter_scope(i + 1, close, frame.depth + 1));
+            }
+
+            i = close + 1;
+            if self.get(i) != Some(&Token::Comma) {
+                break;
+            }
+            i += 1;
+        }
+
+        Step::Exit(i)
+    }
+
+    // Reads the table names starting at `i` into the scope at `index`, a
+    // comma separated list of them if `list` is true. Returns the index
+    // after them.
+    fn tables(&mut self, index: usize, mut i: usize, end: usize, list: bool) -> usize {
+        loop {
+            if self.keyword_at(i, "only") || self.keyword_at(i, "lateral") {
+                i += 1;
+            }
+
+            let mut parts = match self.name(i) {
+                Some(name) if i < end => vec![name],
+                _ => return i,
+            };
+            let mut next = i + 1;
+            while self.get(next) == Some(&Token::Dot) {
+                match self.name(next + 1) {
+                    Some(part) => parts.push(part),
+                    None => break,
+                }
+                next += 2;
+            }
+            if list && self.get(next) == Some(&Token::ParentheseOpen) {
+                // A table function such as generate_series(1, 10)
+                i = self.closing(next, end) + 1;
+            } else {
+                push_unique(&mut self.scopes[index].tables, parts.join("."));
+                i = next;
+            }
+
+            if self.keyword_at(i, "as") {
+                i += 1;
+                if self.name(i).is_some() {
+                    i += 1;
+                }
+            } else if self.is_alias(i) {
+                i += 1;
+            }
+
+            if !list || self.get(i) != Some(&Token::Comma) {
+                return i;
+            }
+            i += 1;
+        }
+    }
+
+    fn name(&self, i: usize) -> Option<String> {
+        let name = match self.get(i)? {
+            Token::Keyword(Keyword::Other(slice))
+            | Token::Backticked(slice)
+            | Token::DoubleQuoted(slice) => self.sql.buffer_content(slice),
+            // Short names are lexed as a type indicator when they're not
+            // followed by a value.
+            Token::LiteralValueTypeIndicator(indicator) => {
+                if let Some(Token::SingleQuoted(_)) = self.get(i + 1) {
+                    return None;
+                }
+                match indicator {
+                    LiteralValueTypeIndicator::Date => "date",
+                    LiteralValueTypeIndicator::Time => "time",
+                    LiteralValueTypeIndicator::Timestamp => "timestamp",
+                    LiteralValueTypeIndicator::X => "x",
+                    LiteralValueTypeIndicator::B => "b",
+                    LiteralValueTypeIndicator::N => "n",
+                    _ => return None,
+                }
+            }
+            _ => return None,
+        };
+        Some(name.to_string())
+    }
+
+    fn is_alias(&self, i: usize) -> bool {
+        self.name(i).is_some()
+            && !NOT_ALIASES
+                .iter()
+                .any(|keyword| self.keyword_at(i, keyword))
+    }
+}
+
+fn push_unique(tables: &mut Vec<String>, table: String) {
+    if !tables.contains(&table) {
+        tables.push(table);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{lex, structure};
+    use super::{Cte, Scope};
+
+    fn tables(scopes: &[Scope]) -> Vec<(usize, Vec<&str>)> {
+        scopes
+            .iter()
+            .map(|scope| {
+                (
+                    scope.depth,
+                    scope.tables.iter().map(|table| table.as_str()).collect(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_simple() {
+        let structure = structure(&lex(
+            "SELECT * FROM `users` u INNER JOIN posts AS p ON p.user_id = u.id, comments c WHERE EXTRACT(YEAR FROM c.date) = 2020".to_string(),
+        ));
+
+        assert_eq!(structure.ctes, vec![]);
+        assert_eq!(structure.max_subquery_depth, 0);
+        assert_eq!(
+            tables(&structure.scopes),
+            vec![(0, vec!["users", "posts", "comments"])]
+        );
+    }
+
+    #[test]
+    fn test_ctes() {
+        // 0: WITH, 2: a, 4: AS, 6: (, 7: SELECT, ..., 21: 1, 22: ), 23: ",", 25: b
+        let sql = lex(
+            "WITH a AS (SELECT * FROM x WHERE y = 1), b AS (SELECT * FROM public.z JOIN a ON a.id = z.id) SELECT * FROM b".to_string(),
+        );
+        let structure = structure(&sql);
+
+        assert_eq!(
+            structure.ctes,
+            vec![
+                Cte {
+                    name: "a".to_string(),
+                    span: 2..23,
+                    tables: vec!["x".to_string()],
+                },
+                Cte {
+                    name: "b".to_string(),
+                    span: 25..sql.tokens.len() - 8,
+                    tables: vec!["public.z".to_string(), "a".to_string()],
+                },
+            ]
+        );
+        assert_eq!(structure.max_subquery_depth, 1);
+        assert_eq!(
+            tables(&structure.scopes),
+            vec![(0, vec!["b"]), (1, vec!["x"]), (1, vec!["public.z", "a"])]
+        );
+        assert_eq!(structure.scopes[1].span, 7..22);
+    }
+
+    #[test]
+    fn test_recursive_cte() {
+        let structure = structure(&lex(
+            "WITH RECURSIVE t(n) AS NOT MATERIALIZED (SELECT 1 UNION ALL SELECT n + 1 FROM t) SELECT n FROM t".to_string(),
+        ));
+
+        assert_eq!(
+            structure
+                .ctes
+                .iter()
+                .map(|cte| (cte.name.as_str(), cte.tables.clone()))
+                .collect::<Vec<_>>(),
+            vec![("t", vec!["t".to_string()])]
+        );
+    }
+
+    #[test]
+    fn test_nested_subqueries() {
+        let structure = structure(&lex(
+            "SELECT * FROM a WHERE id IN (SELECT a_id FROM b WHERE EXISTS (SELECT 1 FROM c WHERE c.b_id = b.id)) AND x = (SELECT MAX(x) FROM (SELECT x FROM d) sub)".to_string(),
+        ));
+
+        assert_eq!(structure.max_subquery_depth, 2);
+        assert_eq!(
+            tables(&structure.scopes),
+            vec![
+                (0, vec!["a"]),
+                (1, vec!["b"]),
+                (2, vec!["c"]),
+                (1, vec![]),
+                (2, vec!["d"]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_deeply_nested_subqueries() {
+        let depth = structure(&lex(format!(
+            "SELECT * FROM t WHERE a IN {}",
+            "(SELECT a FROM t WHERE a IN ".repeat(50_000)
+        )))
+        .max_subquery_depth;
+        assert_eq!(depth, 50_000);
+
+        let ctes = structure(&lex(format!(
+            "{}SELECT 1",
+            "WITH a AS (SELECT * FROM t, (".repeat(2_000)
+        )))
+        .ctes;
+        assert_eq!(ctes.len(), 2_000);
+        assert_eq!(ctes[0].tables, vec!["t".to_string()]);
+    }
+
+    #[test]
+    fn test_statements() {
+        let structure = structure(&lex(
+            "INSERT INTO t (a, b) SELECT a, b FROM generate_series(1, 10), u; UPDATE v SET a = 1; DELETE FROM w".to_string(),
+        ));
+
+        assert_eq!(
+            tables(&structure.scopes),
+            vec![(0, vec!["t", "u"]), (0, vec!["v"]), (0, vec!["w"])]
+        );
+    }
+}
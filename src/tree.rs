@@ -0,0 +1,247 @@
+use super::{Sql, Token};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Delimiter {
+    Parenthese,    // ( )
+    SquareBracket, // [ ]
+}
+
+/// A pair of parentheses or square brackets and everything in between.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Group {
+    pub delimiter: Delimiter,
+    /// Position of the opening token in `Sql::tokens`
+    pub open: usize,
+    /// Position of the closing token in `Sql::tokens`, `None` if the group
+    /// is never closed.
+    pub close: Option<usize>,
+    /// Nesting depth, top level groups have a depth of 1
+    pub depth: usize,
+    /// Index in `TokenTree::groups` of the group this group is nested in
+    pub parent: Option<usize>,
+}
+
+/// Groups the flat list of tokens in a `Sql` struct by matching
+/// parentheses and square brackets. A closing token that doesn't match
+/// the innermost open group is treated as a regular token.
+///
+/// Groups are stored flat and refer to each other by index, so neither
+/// building nor dropping the tree recurses however deep the nesting is.
+#[derive(Debug, PartialEq)]
+pub struct TokenTree {
+    groups: Vec<Group>,
+    // For every token the depth of the innermost group it is in
+    depths: Vec<usize>,
+    // For every token the position of the opening token of the innermost
+    // group it is in
+    enclosing: Vec<Option<usize>>,
+    // For every opening token the position of its closing token
+    closing: Vec<Option<usize>>,
+}
+
+impl TokenTree {
+    pub fn new(sql: &Sql) -> TokenTree {
+        let len = sql.tokens.len();
+        let mut groups: Vec<Group> = Vec::new();
+        let mut depths = vec![0; len];
+        let mut enclosing = vec![None; len];
+        let mut closing = vec![None; len];
+        // Indexes in `groups` of the groups that are still open
+        let mut open_groups: Vec<usize> = Vec::new();
+
+        for (pos, token) in sql.tokens.iter().enumerate() {
+            let delimiter = match token {
+                Token::ParentheseOpen | Token::ParentheseClose => Some(Delimiter::Parenthese),
+                Token::SquareBracketOpen | Token::SquareBracketClose => {
+                    Some(Delimiter::SquareBracket)
+                }
+                _ => None,
+            };
+
+            match (token, delimiter) {
+                (Token::ParentheseOpen | Token::SquareBracketOpen, Some(delimiter)) => {
+                    let depth = open_groups.len() + 1;
+                    depths[pos] = depth;
+                    enclosing[pos] = Some(pos);
+                    open_groups.push(groups.len());
+                    groups.push(Group {
+                        delimiter,
+                        open: pos,
+                        close: None,
+                        depth,
+                        parent: open_groups.iter().rev().nth(1).copied(),
+                    });
+                }
+                (Token::ParentheseClose | Token::SquareBracketClose, Some(delimiter))
+                    if open_groups.last().map(|index| groups[*index].delimiter)
+                        == Some(delimiter) =>
+                {
+                    let group = &mut groups[open_groups.pop().expect("group is open")];
+                    group.close = Some(pos);
+                    depths[pos] = group.depth;
+                    enclosing[pos] = Some(group.open);
+                    closing[group.open] = Some(pos);
+                }
+                _ => {
+                    depths[pos] = open_groups.len();
+                    enclosing[pos] = open_groups.last().map(|index| groups[*index].open);
+                }
+            }
+        }
+
+        TokenTree {
+            groups,
+            depths,
+            enclosing,
+            closing,
+        }
+    }
+
+    /// Nesting depth of the token at `position`. The opening and closing
+    /// tokens of a group have the depth of the group itself.
+    pub fn depth(&self, position: usize) -> usize {
+        self.depths.get(position).copied().unwrap_or(0)
+    }
+
+    /// The deepest nesting in the query
+    pub fn max_depth(&self) -> usize {
+        self.depths.iter().copied().max().unwrap_or(0)
+    }
+
+    /// Position of the opening token of the innermost group the token at
+    /// `position` is in.
+    pub fn enclosing(&self, position: usize) -> Option<usize> {
+        self.enclosing.get(position).copied().flatten()
+    }
+
+    /// Position of the closing token of the group opened at `open`.
+    pub fn closing(&self, open: usize) -> Option<usize> {
+        self.closing.get(open).copied().flatten()
+    }
+
+    /// All groups in the order they are opened in
+    pub fn groups(&self) -> &[Group] {
+        &self.groups
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::lex;
+    use super::{Delimiter, Group, TokenTree};
+
+    #[test]
+    fn test_flat() {
+        let tree = TokenTree::new(&lex("SELECT *".to_string()));
+
+        assert_eq!(tree.max_depth(), 0);
+        assert_eq!(tree.groups(), &[]);
+    }
+
+    #[test]
+    fn test_nested() {
+        // 0: a, 1: (, 2: b, 3: [, 4: c, 5: ], 6: ), 7: d
+        let tree = TokenTree::new(&lex("a(b[c])d".to_string()));
+
+        assert_eq!(
+            tree.groups(),
+            &[
+                Group {
+                    delimiter: Delimiter::Parenthese,
+                    open: 1,
+                    close: Some(6),
+                    depth: 1,
+                    parent: None,
+                },
+                Group {
+                    delimiter: Delimiter::SquareBracket,
+                    open: 3,
+                    close: Some(5),
+                    depth: 2,
+                    parent: Some(0),
+                },
+            ]
+        );
+        assert_eq!(
+            (0..8).map(|pos| tree.depth(pos)).collect::<Vec<_>>(),
+            vec![0, 1, 1, 2, 2, 2, 1, 0]
+        );
+        assert_eq!(tree.max_depth(), 2);
+        assert_eq!(tree.enclosing(4), Some(3));
+        assert_eq!(tree.enclosing(2), Some(1));
+        assert_eq!(tree.enclosing(7), None);
+        assert_eq!(tree.closing(1), Some(6));
+        assert_eq!(tree.closing(3), Some(5));
+    }
+
+    #[test]
+    fn test_siblings() {
+        // 0: (, 1: (, 2: ), 3: (, 4: ), 5: )
+        let tree = TokenTree::new(&lex("(()())".to_string()));
+
+        assert_eq!(
+            tree.groups()
+                .iter()
+                .map(|group| (group.open, group.parent))
+                .collect::<Vec<_>>(),
+            vec![(0, None), (1, Some(0)), (3, Some(0))]
+        );
+    }
+
+    #[test]
+    fn test_subquery_in_function() {
+        let sql = lex("COMMAND(x, (SELECT a FROM b), 'c')".to_string());
+        let tree = TokenTree::new(&sql);
+
+        // The last argument is still in the function call
+        assert_eq!(tree.enclosing(sql.tokens.len() - 2), Some(1));
+        assert_eq!(tree.closing(1), Some(sql.tokens.len() - 1));
+    }
+
+    #[test]
+    fn test_unclosed() {
+        // 0: (, 1: a, 2: (, 3: b
+        let tree = TokenTree::new(&lex("(a(b".to_string()));
+
+        assert_eq!(
+            tree.groups(),
+            &[
+                Group {
+                    delimiter: Delimiter::Parenthese,
+                    open: 0,
+                    close: None,
+                    depth: 1,
+                    parent: None,
+                },
+                Group {
+                    delimiter: Delimiter::Parenthese,
+                    open: 2,
+                    close: None,
+                    depth: 2,
+                    parent: Some(0),
+                },
+            ]
+        );
+        assert_eq!(tree.closing(0), None);
+        assert_eq!(tree.enclosing(3), Some(2));
+    }
+
+    #[test]
+    fn test_mismatched_close() {
+        // 0: (, 1: a, 2: ], 3: )
+        let tree = TokenTree::new(&lex("(a])".to_string()));
+
+        assert_eq!(tree.depth(2), 1);
+        assert_eq!(tree.closing(0), Some(3));
+    }
+
+    #[test]
+    fn test_deep_nesting() {
+        let sql = lex(format!("SELECT {}", "(".repeat(300_000)));
+        let tree = TokenTree::new(&sql);
+
+        assert_eq!(tree.max_depth(), 300_000);
+        assert_eq!(tree.groups().len(), 300_000);
+        assert_eq!(tree.groups()[299_999].parent, Some(299_998));
+    }
+}
@@ -0,0 +1,289 @@
+use super::sanitizer::{is_body, is_type_modifier, split_dollar_quoted};
+use super::statements::is_significant;
+use super::{lex, write, Keyword, LiteralValueTypeIndicator, Operator, Sql, Token, TokenTree};
+
+/// A value or comment that is still in the written output of a sanitized
+/// query. The leaked content itself is not included.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LeakReport {
+    pub kind: LeakKind,
+    /// Byte offset of the leak in the written query
+    pub position: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LeakKind {
+    SingleQuoted,
+    DollarQuoted,
+    /// A number that is not a row count, frame size, sample size, column
+    /// position or type size
+    Numeric,
+    /// A double quoted value that is compared with something
+    DoubleQuoted,
+    Comment,
+}
+
+pub fn verify_sanitized(sql: &Sql) -> Result<(), Vec<LeakReport>> {
+    let mut leaks = Vec::new();
+    find_leaks(&lex(write(sql.clone())), 0, &mut leaks);
+
+    if leaks.is_empty() {
+        Ok(())
+    } else {
+        Err(leaks)
+    }
+}
+
+// Adds the leaks in the written query to `leaks`, with their positions
+// moved by `offset`. The bodies of functions and DO blocks are checked as
+// queries of their own.
+fn find_leaks(written: &Sql, offset: usize, leaks: &mut Vec<LeakReport>) {
+    let tree = TokenTree::new(written);
+    let mut previous: Option<&Token> = None;
+    let mut before_previous: Option<&Token> = None;
+    // Whether we're in an ORDER BY or GROUP BY clause, which can refer to
+    // columns by their position.
+    let mut in_ordinal_clause = false;
+    // Whether we're in a LIMIT clause, which can have an offset and a row
+    // count as in `LIMIT 10, 20`
+    let mut in_limit = false;
+    for (pos, token) in written.tokens.iter().enumerate() {
+        let leak = match token {
+            Token::SingleQuoted(slice) => Some((LeakKind::SingleQuoted, slice.start - 1)),
+            Token::DollarQuoted(slice) if is_body(written, pos) => {
+                let (tag, body) = split_dollar_quoted(written.buffer_content(slice));
+                find_leaks(
+                    &lex(body.to_string()),
+                    offset + slice.start + tag.len(),
+                    leaks,
+                );
+                None
+            }
+            Token::DollarQuoted(slice) => Some((LeakKind::DollarQuoted, slice.start)),
+            Token::Comment(slice) => Some((LeakKind::Comment, slice.start)),
+            Token::Numeric(slice) => {
+                let allowed = match previous {
+                    Some(Token::Keyword(Keyword::Limit | Keyword::Offset)) => true,
+                    Some(token @ Token::Keyword(Keyword::Other(_))) => {
+                        ["top", "first", "next"]
+                            .iter()
+                            .any(|keyword| written.is_keyword(token, keyword))
+                            || (in_ordinal_clause && written.is_keyword(token, "by"))
+                    }
+                    Some(Token::Comma) => in_ordinal_clause || in_limit,
+                    // LIMIT (10), TOP (10) and TABLESAMPLE BERNOULLI (5)
+                    Some(Token::ParentheseOpen) => before_previous.is_some_and(|token| {
+                        *token == Token::Keyword(Keyword::Limit)
+                            || written.is_keyword(token, "top")
+                            || written.is_keyword(token, "bernoulli")
+                            || written.is_keyword(token, "system")
+                    }),
+                    _ => false,
+                };
+                // Type sizes such as varchar(10) in `::varchar(10)`
+                let allowed = allowed
+                    || tree
+                        .enclosing(pos)
+                        .is_some_and(|open| is_type_modifier(written, &tree, open));
+                // Window frame sizes such as 5 PRECEDING
+                let allowed = allowed
+                    || written.tokens[pos + 1..]
+                        .iter()
+                        .find(|token| is_significant(token))
+                        .is_some_and(|next| {
+                            written.is_keyword(next, "preceding")
+                                || written.is_keyword(next, "following")
+                        });
+                if allowed {
+                    None
+                } else {
+                    Some((LeakKind::Numeric, slice.start))
+                }
+            }
+            // The lexer splits 0xFF into `0x` and FF
+            Token::LiteralValueTypeIndicator(LiteralValueTypeIndicator::ZeroX) => {
+                match written.tokens.get(pos + 1) {
+                    Some(Token::Keyword(Keyword::Other(tail))) if tail.start >= 2 => {
+                        Some((LeakKind::Numeric, tail.start - 2))
+                    }
+                    _ => None,
+                }
+            }
+            Token::DoubleQuoted(slice) => {
+                let compared = matches!(previous, Some(Token::Operator(Operator::Comparison(_))));
+                let identifier = matches!(written.tokens.get(pos + 1), Some(Token::Dot));
+                if compared && !identifier {
+                    Some((LeakKind::DoubleQuoted, slice.start - 1))
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        };
+        if let Some((kind, position)) = leak {
+            leaks.push(LeakReport {
+                kind,
+                position: offset + position,
+            });
+        }
+
+        if is_significant(token) {
+            match token {
+                Token::Keyword(Keyword::Other(_))
+                    if written.is_keyword(token, "order") || written.is_keyword(token, "group") =>
+                {
+                    in_ordinal_clause = true
+                }
+                Token::Keyword(Keyword::Other(_)) if written.is_keyword(token, "by") => (),
+                Token::Keyword(Keyword::Limit) => {
+                    in_ordinal_clause = false;
+                    in_limit = true;
+                }
+                Token::Keyword(_)
+                | Token::ParentheseOpen
+                | Token::ParentheseClose
+                | Token::Semicolon => {
+                    in_ordinal_clause = false;
+                    in_limit = false;
+                }
+                _ => (),
+            }
+            before_previous = previous;
+            previous = Some(token);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{
+        lex, sanitize, sanitize_with_options, verify_sanitized, write, SanitizeOptions,
+    };
+    use super::{LeakKind, LeakReport};
+
+    fn leaks(sql: &str) -> Vec<LeakKind> {
+        match verify_sanitized(&lex(sql.to_string())) {
+            Ok(()) => Vec::new(),
+            Err(leaks) => leaks.into_iter().map(|leak| leak.kind).collect(),
+        }
+    }
+
+    #[test]
+    fn test_sanitized() {
+        let sql = sanitize(lex(
+            "SELECT * FROM \"users\" WHERE \"users\".\"id\" = 1 AND name = 'bob' /* comment */ ORDER BY 1, 2 DESC LIMIT 10"
+                .to_string(),
+        ));
+
+        assert_eq!(verify_sanitized(&sql), Ok(()));
+    }
+
+    #[test]
+    fn test_leaks() {
+        assert_eq!(
+            leaks("SELECT 'secret', 42, $$body$$ FROM t WHERE a = \"value\" -- comment"),
+            vec![
+                LeakKind::SingleQuoted,
+                LeakKind::Numeric,
+                LeakKind::DollarQuoted,
+                LeakKind::DoubleQuoted,
+                LeakKind::Comment,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_hex_leaks() {
+        assert_eq!(
+            verify_sanitized(&lex("SELECT a FROM t WHERE b = 0xDEADBEEF".to_string())),
+            Err(vec![LeakReport {
+                kind: LeakKind::Numeric,
+                position: 26,
+            }])
+        );
+        assert_eq!(leaks("SELECT 0xFF, 0x1F"), vec![LeakKind::Numeric; 2]);
+        assert_eq!(
+            verify_sanitized(&sanitize(lex(
+                "SELECT a FROM t WHERE b = 0xDEADBEEF".to_string()
+            ))),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn test_allowed_numbers() {
+        assert_eq!(
+            leaks("SELECT TOP 5 a FROM t GROUP BY 1, 2 ORDER BY 3 LIMIT 10 OFFSET 20 FETCH FIRST 1 ROWS ONLY"),
+            vec![]
+        );
+        assert_eq!(
+            leaks("SELECT TOP (5) SUM(a) OVER (ROWS BETWEEN 2 PRECEDING AND 1 FOLLOWING) FROM t TABLESAMPLE SYSTEM (10)"),
+            vec![]
+        );
+        assert_eq!(
+            leaks("SELECT a FROM t LIMIT 10, 20; SELECT a FROM t LIMIT (10)"),
+            vec![]
+        );
+        assert_eq!(
+            leaks("SELECT CAST(a AS numeric(10, 2)), b::varchar(10) FROM t"),
+            vec![]
+        );
+        assert_eq!(
+            leaks("SELECT a FROM t ORDER BY 1, (2)"),
+            vec![LeakKind::Numeric]
+        );
+        assert_eq!(
+            leaks("SELECT a FROM t WHERE b IN (1, 2) LIMIT 10; SELECT f(3, 4)"),
+            vec![LeakKind::Numeric; 4]
+        );
+    }
+
+    #[test]
+    fn test_sanitized_limit() {
+        let sql = sanitize(lex("SELECT * FROM t WHERE a = 1 LIMIT 10, 20".to_string()));
+
+        assert_eq!(
+            write(sql.clone()),
+            "SELECT * FROM t WHERE a = ? LIMIT 10, 20"
+        );
+        assert_eq!(verify_sanitized(&sql), Ok(()));
+    }
+
+    #[test]
+    fn test_bodies() {
+        let sql = "DO $$ BEGIN UPDATE t SET a = 'x'; END $$; SELECT a FROM t WHERE b = $$c$$";
+        let options = SanitizeOptions {
+            sanitize_bodies: true,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            verify_sanitized(&sanitize_with_options(lex(sql.to_string()), &options)),
+            Ok(())
+        );
+        assert_eq!(
+            verify_sanitized(&lex(sql.to_string())),
+            Err(vec![
+                LeakReport {
+                    kind: LeakKind::SingleQuoted,
+                    position: 29,
+                },
+                LeakReport {
+                    kind: LeakKind::DollarQuoted,
+                    position: 68,
+                },
+            ])
+        );
+    }
+
+    #[test]
+    fn test_position() {
+        assert_eq!(
+            verify_sanitized(&lex("SELECT a FROM t WHERE b = 'c'".to_string())),
+            Err(vec![LeakReport {
+                kind: LeakKind::SingleQuoted,
+                position: 26,
+            }])
+        );
+    }
+}
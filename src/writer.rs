@@ -1,195 +1,415 @@
 use super::{
-    ArithmeticOperator, BitwiseOperator, ComparisonOperator, JsonOperator, Keyword,
-    LiteralValueTypeIndicator, LogicalOperator, Operator, Sql, Token,
+    ArithmeticOperator, BitwiseOperator, ComparisonOperator, JsonOperator, Keyword, ListBucket,
+    LiteralValueTypeIndicator, LogicalOperator, Operator, Placeholder, PlaceholderStyle, Sql,
+    StatementRange, Token, ValueKind, WriteOptions,
 };
 
+// Marks the end of a query that was cut off
+const TRUNCATED: &str = "...TRUNCATED";
+
 pub struct SqlWriter {
     pub sql: Sql,
+    options: WriteOptions,
 }
 
 impl SqlWriter {
     pub fn new(sql: Sql) -> SqlWriter {
-        SqlWriter { sql }
+        SqlWriter::with_options(sql, WriteOptions::default())
+    }
+
+    pub fn with_options(sql: Sql, options: WriteOptions) -> SqlWriter {
+        SqlWriter { sql, options }
     }
 
     pub fn write(&self) -> String {
+        self.write_range(&StatementRange {
+            start: 0,
+            end: self.sql.tokens.len(),
+        })
+    }
+
+    /// Write only the tokens in `range`, for example a single statement of
+    /// a script.
+    pub fn write_range(&self, range: &StatementRange) -> String {
         let mut out = String::new();
+        let mut placeholders = self.last_numbered_placeholder();
+
+        for token in self.sql.tokens[range.start..range.end].iter() {
+            self.write_token(&self.sql, token, Some(&mut placeholders), &mut out);
+        }
+
+        out
+    }
+
+    /// Write the sql, but cut it off at a token boundary so the result is
+    /// at most `max_len` bytes long. Open parentheses and square brackets
+    /// are closed and the result is marked with `...TRUNCATED`.
+    pub fn write_truncated(&self, max_len: usize) -> String {
+        let full = self.write();
+        if full.len() <= max_len {
+            return full;
+        }
+
+        let marker_len = TRUNCATED.len() + 1;
+        let mut out = String::new();
+        let mut closing: Vec<char> = Vec::new();
+        let mut written = String::new();
+        let mut placeholders = self.last_numbered_placeholder();
+        // Where the last line comment starts and ends in `out`
+        let mut line_comment = None;
 
         for token in self.sql.tokens.iter() {
-            match *token {
-                // Arithmetic operator
-                Token::Operator(Operator::Arithmetic(ArithmeticOperator::Multiply)) => {
-                    out.push('*')
-                }
-                Token::Operator(Operator::Arithmetic(ArithmeticOperator::Divide)) => out.push('/'),
-                Token::Operator(Operator::Arithmetic(ArithmeticOperator::Modulo)) => out.push('%'),
-                Token::Operator(Operator::Arithmetic(ArithmeticOperator::Plus)) => out.push('+'),
-                Token::Operator(Operator::Arithmetic(ArithmeticOperator::Minus)) => out.push('-'),
-                // Logical operator
-                Token::Operator(Operator::Logical(LogicalOperator::In)) => out.push_str("IN"),
-                Token::Operator(Operator::Logical(LogicalOperator::Not)) => out.push_str("NOT"),
-                Token::Operator(Operator::Logical(LogicalOperator::Like)) => out.push_str("LIKE"),
-                Token::Operator(Operator::Logical(LogicalOperator::Ilike)) => out.push_str("ILIKE"),
-                Token::Operator(Operator::Logical(LogicalOperator::Rlike)) => out.push_str("RLIKE"),
-                Token::Operator(Operator::Logical(LogicalOperator::Glob)) => out.push_str("GLOB"),
-                Token::Operator(Operator::Logical(LogicalOperator::Match)) => out.push_str("MATCH"),
-                Token::Operator(Operator::Logical(LogicalOperator::Then)) => out.push_str("THEN"),
-                Token::Operator(Operator::Logical(LogicalOperator::Else)) => out.push_str("ELSE"),
-                Token::Operator(Operator::Logical(LogicalOperator::Regexp)) => {
-                    out.push_str("REGEXP")
-                }
-                // Comparison operator
-                Token::Operator(Operator::Comparison(ComparisonOperator::Equal)) => out.push('='),
-                Token::Operator(Operator::Comparison(ComparisonOperator::Equal2)) => {
-                    out.push_str("==")
-                }
-                Token::Operator(Operator::Comparison(ComparisonOperator::NullSafeEqual)) => {
-                    out.push_str("<=>")
-                }
-                Token::Operator(Operator::Comparison(ComparisonOperator::GreaterThanOrEqual)) => {
-                    out.push_str(">=")
-                }
-                Token::Operator(Operator::Comparison(ComparisonOperator::LessThanOrEqual)) => {
-                    out.push_str("<=")
-                }
-                Token::Operator(Operator::Comparison(ComparisonOperator::EqualOrGreaterThan)) => {
-                    out.push_str("=>")
-                }
-                Token::Operator(Operator::Comparison(ComparisonOperator::EqualOrLessThan)) => {
-                    out.push_str("<=")
-                }
-                Token::Operator(Operator::Comparison(ComparisonOperator::EqualWithArrows)) => {
-                    out.push_str("<>")
-                }
-                Token::Operator(Operator::Comparison(ComparisonOperator::NotEqual)) => {
-                    out.push_str("!=")
-                }
-                Token::Operator(Operator::Comparison(ComparisonOperator::GreaterThan)) => {
-                    out.push('>')
-                }
-                Token::Operator(Operator::Comparison(ComparisonOperator::LessThan)) => {
-                    out.push('<')
-                }
-                // Bitwise operator
-                Token::Operator(Operator::Bitwise(BitwiseOperator::LeftShift)) => {
-                    out.push_str("<<")
-                }
-                Token::Operator(Operator::Bitwise(BitwiseOperator::RightShift)) => {
-                    out.push_str(">>")
-                }
-                Token::Operator(Operator::Bitwise(BitwiseOperator::And)) => out.push('&'),
-                Token::Operator(Operator::Bitwise(BitwiseOperator::Or)) => out.push('|'),
-                // JSON operator
-                Token::Operator(Operator::Json(JsonOperator::SpecifiedPath)) => out.push_str("#>"),
-                Token::Operator(Operator::Json(JsonOperator::SpecifiedPathAsText)) => {
-                    out.push_str("#>>")
-                }
-                // Keywords
-                Token::Keyword(Keyword::Select) => out.push_str("SELECT"),
-                Token::Keyword(Keyword::From) => out.push_str("FROM"),
-                Token::Keyword(Keyword::Where) => out.push_str("WHERE"),
-                Token::Keyword(Keyword::Update) => out.push_str("UPDATE"),
-                Token::Keyword(Keyword::Set) => out.push_str("SET"),
-                Token::Keyword(Keyword::Insert) => out.push_str("INSERT"),
-                Token::Keyword(Keyword::Into) => out.push_str("INTO"),
-                Token::Keyword(Keyword::Values) => out.push_str("VALUES"),
-                Token::Keyword(Keyword::Inner) => out.push_str("INNER"),
-                Token::Keyword(Keyword::Join) => out.push_str("JOIN"),
-                Token::Keyword(Keyword::On) => out.push_str("ON"),
-                Token::Keyword(Keyword::And) => out.push_str("AND"),
-                Token::Keyword(Keyword::Or) => out.push_str("OR"),
-                Token::Keyword(Keyword::Limit) => out.push_str("LIMIT"),
-                Token::Keyword(Keyword::Offset) => out.push_str("OFFSET"),
-                Token::Keyword(Keyword::Between) => out.push_str("BETWEEN"),
-                Token::Keyword(Keyword::Array) => out.push_str("ARRAY"),
-                Token::Keyword(Keyword::Other(ref slice)) => {
-                    out.push_str(self.sql.buffer_content(slice));
-                }
-                // Literal value type indicator
-                Token::LiteralValueTypeIndicator(LiteralValueTypeIndicator::Binary) => {
-                    out.push_str("BINARY")
-                }
-                Token::LiteralValueTypeIndicator(LiteralValueTypeIndicator::Date) => {
-                    out.push_str("DATE")
-                }
-                Token::LiteralValueTypeIndicator(LiteralValueTypeIndicator::Time) => {
-                    out.push_str("TIME")
-                }
-                Token::LiteralValueTypeIndicator(LiteralValueTypeIndicator::Timestamp) => {
-                    out.push_str("TIMESTAMP")
-                }
-                Token::LiteralValueTypeIndicator(LiteralValueTypeIndicator::X) => out.push('x'),
-                Token::LiteralValueTypeIndicator(LiteralValueTypeIndicator::ZeroX) => {
-                    out.push_str("0x")
-                }
-                Token::LiteralValueTypeIndicator(LiteralValueTypeIndicator::B) => out.push('b'),
-                Token::LiteralValueTypeIndicator(LiteralValueTypeIndicator::ZeroB) => {
-                    out.push_str("0b")
-                }
-                Token::LiteralValueTypeIndicator(LiteralValueTypeIndicator::N) => out.push('n'),
-                Token::LiteralValueTypeIndicator(LiteralValueTypeIndicator::Charset(ref slice)) => {
-                    out.push('_');
-                    out.push_str(self.sql.buffer_content(slice));
-                }
-                // Backticked
-                Token::Backticked(ref slice) => {
-                    out.push('`');
-                    out.push_str(self.sql.buffer_content(slice));
-                    out.push('`');
-                }
-                // Double quoted
-                Token::DoubleQuoted(ref slice) => {
-                    out.push('"');
-                    out.push_str(self.sql.buffer_content(slice));
-                    out.push('"');
+            written.clear();
+            self.write_token(&self.sql, token, Some(&mut placeholders), &mut written);
+
+            let closing_len = match token {
+                Token::ParentheseOpen | Token::SquareBracketOpen => closing.len() + 1,
+                Token::ParentheseClose | Token::SquareBracketClose => {
+                    closing.len().saturating_sub(1)
                 }
-                // Single quoted
-                Token::SingleQuoted(ref slice) => {
-                    out.push('\'');
-                    out.push_str(self.sql.buffer_content(slice));
-                    out.push('\'');
+                _ => closing.len(),
+            };
+            if out.len() + written.len() + closing_len + marker_len > max_len {
+                break;
+            }
+
+            match token {
+                Token::ParentheseOpen => closing.push(')'),
+                Token::SquareBracketOpen => closing.push(']'),
+                Token::ParentheseClose | Token::SquareBracketClose => {
+                    closing.pop();
                 }
-                // Numeric
-                Token::Numeric(ref slice) => {
-                    out.push_str(self.sql.buffer_content(slice));
+                Token::Comment(_) if written.starts_with("--") || written.starts_with('#') => {
+                    line_comment = Some((out.len(), out.len() + written.len()))
                 }
-                // Comment
-                Token::Comment(ref slice) => {
-                    out.push_str(self.sql.buffer_content(slice));
+                _ => (),
+            }
+            out.push_str(&written);
+        }
+
+        // Don't leave a dangling separator before the closing characters
+        let trim = |out: &mut String| {
+            let trimmed_len = out.trim_end_matches(&[' ', '\n', '\r', ','][..]).len();
+            out.truncate(trimmed_len);
+        };
+        trim(&mut out);
+        // Nothing can follow a line comment on the same line, so cut before it
+        // if it's at the end
+        if let Some((start, end)) = line_comment {
+            if out.len() > start && out.len() <= end {
+                out.truncate(start);
+                trim(&mut out);
+            }
+        }
+        while let Some(c) = closing.pop() {
+            out.push(c);
+        }
+
+        if out.is_empty() {
+            if TRUNCATED.len() <= max_len {
+                out.push_str(TRUNCATED);
+            }
+        } else {
+            out.push(' ');
+            out.push_str(TRUNCATED);
+        }
+
+        out
+    }
+
+    // `placeholders` is the number of the last numbered placeholder that was
+    // written, or `None` if placeholders are not numbered.
+    fn write_token(
+        &self,
+        sql: &Sql,
+        token: &Token,
+        mut placeholders: Option<&mut usize>,
+        out: &mut String,
+    ) {
+        match *token {
+            // Arithmetic operator
+            Token::Operator(Operator::Arithmetic(ArithmeticOperator::Multiply)) => out.push('*'),
+            Token::Operator(Operator::Arithmetic(ArithmeticOperator::Divide)) => out.push('/'),
+            Token::Operator(Operator::Arithmetic(ArithmeticOperator::Modulo)) => out.push('%'),
+            Token::Operator(Operator::Arithmetic(ArithmeticOperator::Plus)) => out.push('+'),
+            Token::Operator(Operator::Arithmetic(ArithmeticOperator::Minus)) => out.push('-'),
+            // Logical operator
+            Token::Operator(Operator::Logical(LogicalOperator::In)) => out.push_str("IN"),
+            Token::Operator(Operator::Logical(LogicalOperator::Not)) => out.push_str("NOT"),
+            Token::Operator(Operator::Logical(LogicalOperator::Like)) => out.push_str("LIKE"),
+            Token::Operator(Operator::Logical(LogicalOperator::Ilike)) => out.push_str("ILIKE"),
+            Token::Operator(Operator::Logical(LogicalOperator::Rlike)) => out.push_str("RLIKE"),
+            Token::Operator(Operator::Logical(LogicalOperator::Glob)) => out.push_str("GLOB"),
+            Token::Operator(Operator::Logical(LogicalOperator::Match)) => out.push_str("MATCH"),
+            Token::Operator(Operator::Logical(LogicalOperator::Then)) => out.push_str("THEN"),
+            Token::Operator(Operator::Logical(LogicalOperator::Else)) => out.push_str("ELSE"),
+            Token::Operator(Operator::Logical(LogicalOperator::Regexp)) => out.push_str("REGEXP"),
+            // Comparison operator
+            Token::Operator(Operator::Comparison(ComparisonOperator::Equal)) => out.push('='),
+            Token::Operator(Operator::Comparison(ComparisonOperator::Equal2)) => out.push_str("=="),
+            Token::Operator(Operator::Comparison(ComparisonOperator::NullSafeEqual)) => {
+                out.push_str("<=>")
+            }
+            Token::Operator(Operator::Comparison(ComparisonOperator::GreaterThanOrEqual)) => {
+                out.push_str(">=")
+            }
+            Token::Operator(Operator::Comparison(ComparisonOperator::LessThanOrEqual)) => {
+                out.push_str("<=")
+            }
+            Token::Operator(Operator::Comparison(ComparisonOperator::EqualOrGreaterThan)) => {
+                out.push_str("=>")
+            }
+            Token::Operator(Operator::Comparison(ComparisonOperator::EqualOrLessThan)) => {
+                out.push_str("<=")
+            }
+            Token::Operator(Operator::Comparison(ComparisonOperator::EqualWithArrows)) => {
+                out.push_str("<>")
+            }
+            Token::Operator(Operator::Comparison(ComparisonOperator::NotEqual)) => {
+                out.push_str("!=")
+            }
+            Token::Operator(Operator::Comparison(ComparisonOperator::GreaterThan)) => out.push('>'),
+            Token::Operator(Operator::Comparison(ComparisonOperator::LessThan)) => out.push('<'),
+            // Bitwise operator
+            Token::Operator(Operator::Bitwise(BitwiseOperator::LeftShift)) => out.push_str("<<"),
+            Token::Operator(Operator::Bitwise(BitwiseOperator::RightShift)) => out.push_str(">>"),
+            Token::Operator(Operator::Bitwise(BitwiseOperator::And)) => out.push('&'),
+            Token::Operator(Operator::Bitwise(BitwiseOperator::Or)) => out.push('|'),
+            // JSON operator
+            Token::Operator(Operator::Json(JsonOperator::SpecifiedPath)) => out.push_str("#>"),
+            Token::Operator(Operator::Json(JsonOperator::SpecifiedPathAsText)) => {
+                out.push_str("#>>")
+            }
+            // Keywords
+            Token::Keyword(Keyword::Select) => out.push_str("SELECT"),
+            Token::Keyword(Keyword::From) => out.push_str("FROM"),
+            Token::Keyword(Keyword::Where) => out.push_str("WHERE"),
+            Token::Keyword(Keyword::Update) => out.push_str("UPDATE"),
+            Token::Keyword(Keyword::Set) => out.push_str("SET"),
+            Token::Keyword(Keyword::Insert) => out.push_str("INSERT"),
+            Token::Keyword(Keyword::Into) => out.push_str("INTO"),
+            Token::Keyword(Keyword::Values) => out.push_str("VALUES"),
+            Token::Keyword(Keyword::Inner) => out.push_str("INNER"),
+            Token::Keyword(Keyword::Join) => out.push_str("JOIN"),
+            Token::Keyword(Keyword::On) => out.push_str("ON"),
+            Token::Keyword(Keyword::And) => out.push_str("AND"),
+            Token::Keyword(Keyword::Or) => out.push_str("OR"),
+            Token::Keyword(Keyword::Limit) => out.push_str("LIMIT"),
+            Token::Keyword(Keyword::Offset) => out.push_str("OFFSET"),
+            Token::Keyword(Keyword::Between) => out.push_str("BETWEEN"),
+            Token::Keyword(Keyword::Array) => out.push_str("ARRAY"),
+            Token::Keyword(Keyword::Other(ref slice)) => {
+                out.push_str(sql.buffer_content(slice));
+            }
+            // Literal value type indicator
+            Token::LiteralValueTypeIndicator(LiteralValueTypeIndicator::Binary) => {
+                out.push_str("BINARY")
+            }
+            Token::LiteralValueTypeIndicator(LiteralValueTypeIndicator::Date) => {
+                out.push_str("DATE")
+            }
+            Token::LiteralValueTypeIndicator(LiteralValueTypeIndicator::Time) => {
+                out.push_str("TIME")
+            }
+            Token::LiteralValueTypeIndicator(LiteralValueTypeIndicator::Timestamp) => {
+                out.push_str("TIMESTAMP")
+            }
+            Token::LiteralValueTypeIndicator(LiteralValueTypeIndicator::X) => out.push('x'),
+            Token::LiteralValueTypeIndicator(LiteralValueTypeIndicator::ZeroX) => {
+                out.push_str("0x")
+            }
+            Token::LiteralValueTypeIndicator(LiteralValueTypeIndicator::B) => out.push('b'),
+            Token::LiteralValueTypeIndicator(LiteralValueTypeIndicator::ZeroB) => {
+                out.push_str("0b")
+            }
+            Token::LiteralValueTypeIndicator(LiteralValueTypeIndicator::N) => out.push('n'),
+            Token::LiteralValueTypeIndicator(LiteralValueTypeIndicator::Charset(ref slice)) => {
+                out.push('_');
+                out.push_str(sql.buffer_content(slice));
+            }
+            // Backticked
+            Token::Backticked(ref slice) => {
+                out.push('`');
+                out.push_str(sql.buffer_content(slice));
+                out.push('`');
+            }
+            // Double quoted
+            Token::DoubleQuoted(ref slice) => {
+                out.push('"');
+                out.push_str(sql.buffer_content(slice));
+                out.push('"');
+            }
+            // Single quoted
+            Token::SingleQuoted(ref slice) => {
+                out.push('\'');
+                out.push_str(sql.buffer_content(slice));
+                out.push('\'');
+            }
+            // Dollar quoted
+            Token::DollarQuoted(ref slice) => {
+                out.push_str(sql.buffer_content(slice));
+            }
+            // Sanitized body. Numbered placeholders such as `$1` in it are the
+            // arguments of the function, so its placeholders are not numbered.
+            Token::DollarQuotedBody(ref tag, ref body) => {
+                let tag = sql.buffer_content(tag);
+                out.push_str(tag);
+                for token in body.tokens.iter() {
+                    self.write_token(body, token, None, out);
                 }
-                // Generic tokens
-                Token::Space => out.push(' '),
-                Token::Newline => out.push('\n'),
-                Token::Dot => out.push('.'),
-                Token::Comma => out.push(','),
-                Token::Wildcard => out.push('*'),
-                Token::ParentheseOpen => out.push('('),
-                Token::ParentheseClose => out.push(')'),
-                Token::SquareBracketOpen => out.push('['),
-                Token::SquareBracketClose => out.push(']'),
-                Token::Colon => out.push(':'),
-                Token::Semicolon => out.push(';'),
-                Token::Placeholder => out.push('?'),
-                Token::Ellipsis => out.push_str("..."),
-                Token::None => {}
-                Token::Null => out.push_str("NULL"),
-                Token::True => out.push_str("TRUE"),
-                Token::False => out.push_str("FALSE"),
-                Token::NumberedPlaceholder(ref slice) => {
-                    out.push_str(self.sql.buffer_content(slice));
+                out.push_str(tag);
+            }
+            // Numeric
+            Token::Numeric(ref slice) => {
+                out.push_str(sql.buffer_content(slice));
+            }
+            // Comment
+            Token::Comment(ref slice) => {
+                out.push_str(sql.buffer_content(slice));
+            }
+            // Generic tokens
+            Token::Space => out.push(' '),
+            Token::Newline => out.push('\n'),
+            Token::Dot => out.push('.'),
+            Token::Comma => out.push(','),
+            Token::Wildcard => out.push('*'),
+            Token::ParentheseOpen => out.push('('),
+            Token::ParentheseClose => out.push(')'),
+            Token::SquareBracketOpen => out.push('['),
+            Token::SquareBracketClose => out.push(']'),
+            Token::Colon => out.push(':'),
+            Token::Semicolon => out.push(';'),
+            Token::Placeholder(placeholder) => {
+                self.write_placeholder(placeholder, placeholders.as_deref_mut(), out)
+            }
+            Token::PlaceholderList(placeholder, counts) => {
+                self.write_placeholder(placeholder, placeholders.as_deref_mut(), out);
+                self.write_count(counts.total(), out);
+            }
+            Token::PlaceholderBucket(placeholder, bucket, _) => {
+                let count = match bucket {
+                    ListBucket::One => 1,
+                    ListBucket::UpToTen => 2,
+                    ListBucket::UpToHundred => 3,
+                    ListBucket::MoreThanHundred => 4,
+                };
+                for i in 0..count {
+                    if i > 0 {
+                        out.push_str(", ");
+                    }
+                    self.write_placeholder(placeholder, placeholders.as_deref_mut(), out);
                 }
-                Token::Unknown(c) => {
-                    out.push(c);
+                if count > 1 {
+                    out.push('+');
                 }
             }
+            Token::Ellipsis(count, _) => {
+                out.push_str("...");
+                self.write_count(count, out);
+            }
+            Token::None => {}
+            Token::Truncated => out.push_str(TRUNCATED),
+            Token::Null => out.push_str("NULL"),
+            Token::True => out.push_str("TRUE"),
+            Token::False => out.push_str("FALSE"),
+            Token::NumberedPlaceholder(ref slice) => {
+                out.push_str(sql.buffer_content(slice));
+            }
+            Token::Unknown(c) => {
+                out.push(c);
+            }
         }
+    }
 
-        out
+    fn write_placeholder(
+        &self,
+        placeholder: Placeholder,
+        placeholders: Option<&mut usize>,
+        out: &mut String,
+    ) {
+        let style = match placeholders {
+            Some(_) => self.options.placeholder_style,
+            None => PlaceholderStyle::QuestionMark,
+        };
+        let prefix = match style {
+            PlaceholderStyle::QuestionMark => "?",
+            PlaceholderStyle::Percent => "%s",
+            PlaceholderStyle::Dollar => "$",
+            PlaceholderStyle::Colon => ":",
+            PlaceholderStyle::AtP => "@p",
+        };
+        out.push_str(prefix);
+        if let (
+            Some(placeholders),
+            PlaceholderStyle::Dollar | PlaceholderStyle::Colon | PlaceholderStyle::AtP,
+        ) = (placeholders, style)
+        {
+            *placeholders += 1;
+            out.push_str(&placeholders.to_string());
+        }
+
+        if let (true, Placeholder::Redacted(kind)) = (self.options.typed_placeholders, placeholder)
+        {
+            out.push_str(match kind {
+                ValueKind::String => ":str",
+                ValueKind::Number => ":num",
+                ValueKind::Null => ":null",
+                ValueKind::Boolean => ":bool",
+                ValueKind::Date => ":date",
+                ValueKind::Hex => ":hex",
+                ValueKind::Unknown => "",
+            });
+        }
+    }
+
+    // The highest number of the placeholders that were already in the query
+    // in the style we write them in, `$2`, `:2` or `@p2`, so we can continue
+    // numbering after it.
+    fn last_numbered_placeholder(&self) -> usize {
+        let tokens = &self.sql.tokens;
+        (0..tokens.len())
+            .filter_map(|pos| {
+                let number = match (self.options.placeholder_style, &tokens[pos]) {
+                    (PlaceholderStyle::Dollar, Token::NumberedPlaceholder(slice)) => {
+                        &self.sql.buffer_content(slice)[1..]
+                    }
+                    // Not the `::` of a cast
+                    (PlaceholderStyle::Colon, Token::Colon)
+                        if pos == 0 || tokens[pos - 1] != Token::Colon =>
+                    {
+                        match tokens.get(pos + 1) {
+                            Some(Token::Numeric(slice)) => self.sql.buffer_content(slice),
+                            _ => return None,
+                        }
+                    }
+                    (PlaceholderStyle::AtP, Token::Unknown('@')) => match tokens.get(pos + 1) {
+                        Some(Token::Keyword(Keyword::Other(slice))) => {
+                            self.sql.buffer_content(slice).strip_prefix(['p', 'P'])?
+                        }
+                        _ => return None,
+                    },
+                    _ => return None,
+                };
+                number.parse().ok()
+            })
+            .max()
+            .unwrap_or(0)
+    }
+
+    // Number of collapsed values or value lists, as a comment
+    fn write_count(&self, count: usize, out: &mut String) {
+        if self.options.show_collapsed_counts {
+            out.push_str(" /*");
+            out.push_str(&count.to_string());
+            out.push_str("*/");
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use super::super::{PlaceholderStyle, WriteOptions};
+
     #[test]
     fn test_write_single_quoted() {
         let sql = "SELECT `table`.* FROM `table` WHERE `id` = 'secret';";
@@ -214,6 +434,14 @@ mod tests {
         assert_eq!(written, sql);
     }
 
+    #[test]
+    fn test_write_dollar_quoted() {
+        let sql = "SELECT $$value$$, $body$value$body$;";
+        let written = helpers::lex_and_write(sql.to_string());
+
+        assert_eq!(written, sql);
+    }
+
     #[test]
     fn test_write_numeric() {
         let sql = "SELECT \"table\".* FROM \"table\" WHERE \"id\" = 1;";
@@ -358,9 +586,249 @@ mod tests {
         assert_eq!(written, sql);
     }
 
+    #[test]
+    fn test_write_collapsed_counts() {
+        let sql = "INSERT INTO `table` (`a`) VALUES (1), (2), (3); SELECT * FROM `table` WHERE `id` IN (1, 2, 3, 4)";
+        let options = WriteOptions {
+            show_collapsed_counts: true,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            helpers::sanitize_and_write_with_options(sql.to_string(), &options),
+            "INSERT INTO `table` (`a`) VALUES (?), ... /*2*/; SELECT * FROM `table` WHERE `id` IN (? /*4*/)"
+        );
+        assert_eq!(
+            helpers::sanitize_and_write_with_options(sql.to_string(), &WriteOptions::default()),
+            "INSERT INTO `table` (`a`) VALUES (?), ...; SELECT * FROM `table` WHERE `id` IN (?)"
+        );
+    }
+
+    #[test]
+    fn test_write_placeholder_styles() {
+        let sql = "SELECT * FROM `table` WHERE `a` = 1 AND `b` IN (1, 2) AND `c` = 'd'";
+        let write = |placeholder_style| {
+            let options = WriteOptions {
+                placeholder_style,
+                ..Default::default()
+            };
+            helpers::sanitize_and_write_with_options(sql.to_string(), &options)
+        };
+
+        assert_eq!(
+            write(PlaceholderStyle::QuestionMark),
+            "SELECT * FROM `table` WHERE `a` = ? AND `b` IN (?) AND `c` = ?"
+        );
+        assert_eq!(
+            write(PlaceholderStyle::Dollar),
+            "SELECT * FROM `table` WHERE `a` = $1 AND `b` IN ($2) AND `c` = $3"
+        );
+        assert_eq!(
+            write(PlaceholderStyle::Colon),
+            "SELECT * FROM `table` WHERE `a` = :1 AND `b` IN (:2) AND `c` = :3"
+        );
+        assert_eq!(
+            write(PlaceholderStyle::AtP),
+            "SELECT * FROM `table` WHERE `a` = @p1 AND `b` IN (@p2) AND `c` = @p3"
+        );
+        assert_eq!(
+            write(PlaceholderStyle::Percent),
+            "SELECT * FROM `table` WHERE `a` = %s AND `b` IN (%s) AND `c` = %s"
+        );
+    }
+
+    #[test]
+    fn test_write_sanitized_body() {
+        let sql = super::super::sanitize_with_options(
+            super::super::lex(
+                "CREATE FUNCTION f(a int) RETURNS int AS $$ SELECT b FROM t WHERE c = $1 AND d = 'e' $$ LANGUAGE sql; SELECT f(1) WHERE g = 'h'"
+                    .to_string(),
+            ),
+            &super::super::SanitizeOptions {
+                sanitize_bodies: true,
+                ..Default::default()
+            },
+        );
+        let options = WriteOptions {
+            placeholder_style: PlaceholderStyle::Dollar,
+            ..Default::default()
+        };
+
+        // `$1` in the body is the argument of the function, so placeholders
+        // in the body are not numbered
+        assert_eq!(
+            super::super::write_with_options(sql, &options),
+            "CREATE FUNCTION f(a int) RETURNS int AS $$ SELECT b FROM t WHERE c = $1 AND d = ? $$ LANGUAGE sql; SELECT f($1) WHERE g = $2"
+        );
+    }
+
+    #[test]
+    fn test_write_placeholder_style_continues_numbering() {
+        let options = WriteOptions {
+            placeholder_style: PlaceholderStyle::Dollar,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            helpers::sanitize_and_write_with_options(
+                "SELECT * FROM \"table\" WHERE a = $2 AND b = 'c' AND d = $1".to_string(),
+                &options
+            ),
+            "SELECT * FROM \"table\" WHERE a = $2 AND b = $3 AND d = $1"
+        );
+
+        let options = WriteOptions {
+            placeholder_style: PlaceholderStyle::Colon,
+            ..Default::default()
+        };
+        assert_eq!(
+            helpers::sanitize_and_write_with_options(
+                "SELECT * FROM t WHERE a = :1 AND b = 'x' AND c = d::int".to_string(),
+                &options
+            ),
+            "SELECT * FROM t WHERE a = :1 AND b = :2 AND c = d::int"
+        );
+
+        let options = WriteOptions {
+            placeholder_style: PlaceholderStyle::AtP,
+            ..Default::default()
+        };
+        assert_eq!(
+            helpers::sanitize_and_write_with_options(
+                "SELECT * FROM t WHERE a = @p2 AND b = 'x' AND c = @P1".to_string(),
+                &options
+            ),
+            "SELECT * FROM t WHERE a = @p2 AND b = @p3 AND c = @P1"
+        );
+    }
+
+    #[test]
+    fn test_write_typed_placeholders() {
+        let sql = "SELECT * FROM t WHERE a = 'b' AND c IN (1, 2) AND d > DATE '2024-01-01' AND e = X'1F' AND f IS NULL AND g = TRUE AND h = ?";
+        let options = WriteOptions {
+            typed_placeholders: true,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            helpers::sanitize_and_write_with_options(sql.to_string(), &options),
+            "SELECT * FROM t WHERE a = ?:str AND c IN (?:num) AND d > DATE ?:date AND e = x?:hex AND f IS NULL AND g = ?:bool AND h = ?"
+        );
+    }
+
+    #[test]
+    fn test_write_typed_placeholders_with_style() {
+        let options = WriteOptions {
+            typed_placeholders: true,
+            placeholder_style: PlaceholderStyle::Dollar,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            helpers::sanitize_and_write_with_options(
+                "UPDATE t SET a = NULL WHERE b = 1".to_string(),
+                &options
+            ),
+            "UPDATE t SET a = $1:null WHERE b = $2:num"
+        );
+    }
+
+    #[test]
+    fn test_write_truncated_fits() {
+        let sql = "SELECT * FROM `table` WHERE `id` = 1;";
+        let written = helpers::lex_and_write_truncated(sql.to_string(), sql.len());
+
+        assert_eq!(written, sql);
+    }
+
+    #[test]
+    fn test_write_truncated() {
+        let sql = "SELECT * FROM `table` WHERE `id` = 1 AND `name` = 'something';";
+        let written = helpers::lex_and_write_truncated(sql.to_string(), 40);
+
+        assert_eq!(written, "SELECT * FROM `table` WHERE ...TRUNCATED");
+        assert!(written.len() <= 40);
+    }
+
+    #[test]
+    fn test_write_truncated_closes_parentheses() {
+        let sql =
+            "SELECT * FROM `table` WHERE `id` IN (SELECT `id` FROM `other` WHERE `a` IN (1, 2, 3))";
+        let written = helpers::lex_and_write_truncated(sql.to_string(), 80);
+
+        assert_eq!(
+            written,
+            "SELECT * FROM `table` WHERE `id` IN (SELECT `id` FROM `other`) ...TRUNCATED"
+        );
+        assert!(written.len() <= 80);
+    }
+
+    #[test]
+    fn test_write_truncated_closes_square_brackets() {
+        let sql = "SELECT ARRAY['item_1','item_2','item_3']";
+        let written = helpers::lex_and_write_truncated(sql.to_string(), 36);
+
+        assert_eq!(written, "SELECT ARRAY['item_1'] ...TRUNCATED");
+    }
+
+    #[test]
+    fn test_write_truncated_multibyte_characters() {
+        let sql = "SELECT 'hæld', 'jæld'";
+        let written = helpers::lex_and_write_truncated(sql.to_string(), 20);
+
+        assert_eq!(written, "SELECT ...TRUNCATED");
+    }
+
+    #[test]
+    fn test_write_truncated_after_line_comment() {
+        let sql = "SELECT (a -- c\n, b) FROM t WHERE x = 1";
+
+        assert_eq!(
+            helpers::lex_and_write_truncated(sql.to_string(), 28),
+            "SELECT (a) ...TRUNCATED"
+        );
+        assert_eq!(
+            helpers::lex_and_write_truncated("SELECT a # c\nFROM t WHERE b = 1".to_string(), 27),
+            "SELECT a ...TRUNCATED"
+        );
+        // A block comment ends before the closing characters
+        assert_eq!(
+            helpers::lex_and_write_truncated(
+                "SELECT (a /* c */, b) FROM t WHERE x = 1".to_string(),
+                32
+            ),
+            "SELECT (a /* c */) ...TRUNCATED"
+        );
+    }
+
+    #[test]
+    fn test_write_truncated_too_short_for_content() {
+        let sql = "SELECT * FROM `table`";
+
+        assert_eq!(
+            helpers::lex_and_write_truncated(sql.to_string(), 12),
+            "...TRUNCATED"
+        );
+        assert_eq!(helpers::lex_and_write_truncated(sql.to_string(), 5), "");
+    }
+
     mod helpers {
         pub fn lex_and_write(sql: String) -> String {
             super::super::super::write(super::super::super::lex(sql))
         }
+
+        pub fn sanitize_and_write_with_options(
+            sql: String,
+            options: &super::super::super::WriteOptions,
+        ) -> String {
+            super::super::super::write_with_options(
+                super::super::super::sanitize(super::super::super::lex(sql)),
+                options,
+            )
+        }
+
+        pub fn lex_and_write_truncated(sql: String, max_len: usize) -> String {
+            super::super::super::write_truncated(super::super::super::lex(sql), max_len)
+        }
     }
 }